@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS Users (
+    id int NOT NULL UNIQUE PRIMARY KEY,
+    username varchar(255) NOT NULL UNIQUE,
+    password varchar(255) NOT NULL,
+    balance int NOT NULL
+);
@@ -0,0 +1,23 @@
+-- SQLite can't change a primary key in place, so the table is rebuilt
+CREATE TABLE Users_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username varchar(255) NOT NULL UNIQUE COLLATE NOCASE,
+    password varchar(255) NOT NULL,
+    balance int NOT NULL
+);
+
+-- Usernames are now unique whatever their case, so any that only differed by
+-- case keep the oldest as it is and have the id added to the rest. Registration
+-- doesn't allow '~', so the new names can't already be taken, and the start is
+-- cut short to keep them within 20 characters
+INSERT INTO Users_new (id, username, password, balance)
+SELECT id,
+    CASE WHEN EXISTS (
+        SELECT 1 FROM Users AS Earlier
+        WHERE Earlier.username = Users.username COLLATE NOCASE AND Earlier.id < Users.id
+    ) THEN substr(username, 1, 19 - length(id)) || '~' || id ELSE username END,
+    password, balance
+FROM Users;
+
+DROP TABLE Users;
+ALTER TABLE Users_new RENAME TO Users;
@@ -0,0 +1,7 @@
+-- Failed login attempts, keyed by either `user:<name>` or `ip:<address>`
+CREATE TABLE LoginAttempts (
+    key varchar(255) NOT NULL PRIMARY KEY,
+    failures int NOT NULL,
+    last_failure int NOT NULL,
+    locked_until int NOT NULL
+);
@@ -0,0 +1,6 @@
+ALTER TABLE Users ADD COLUMN role varchar(16) NOT NULL DEFAULT 'player'
+    CHECK (role IN ('player', 'moderator', 'admin'));
+ALTER TABLE Users ADD COLUMN banned boolean NOT NULL DEFAULT 0;
+
+-- Someone has to be able to hand out roles
+UPDATE Users SET role = 'admin' WHERE username = 'admin';
@@ -0,0 +1,14 @@
+-- Every change to a balance, and why it happened
+CREATE TABLE Ledger (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id int NOT NULL REFERENCES Users(id),
+    amount int NOT NULL,
+    balance_after int NOT NULL,
+    kind varchar(16) NOT NULL,
+    room varchar(6),
+    note text,
+    actor_id int REFERENCES Users(id),
+    created_at int NOT NULL
+);
+
+CREATE INDEX LedgerByUser ON Ledger (user_id, created_at);
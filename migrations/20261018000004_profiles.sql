@@ -0,0 +1,4 @@
+-- How a user appears to the people they play with
+ALTER TABLE Users ADD COLUMN display_name varchar(24);
+ALTER TABLE Users ADD COLUMN avatar varchar(16) NOT NULL DEFAULT 'spade'
+    CHECK (avatar IN ('spade', 'heart', 'diamond', 'club', 'star', 'crown', 'dice', 'chip'));
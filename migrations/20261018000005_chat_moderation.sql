@@ -0,0 +1,13 @@
+-- Table chat moderation: muted users, and messages players have reported
+ALTER TABLE Users ADD COLUMN muted_until int;
+
+CREATE TABLE ChatReports (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    room varchar(6) NOT NULL,
+    reporter_id int NOT NULL REFERENCES Users(id),
+    reported_id int NOT NULL REFERENCES Users(id),
+    text text NOT NULL,
+    sent_at int NOT NULL,
+    created_at int NOT NULL,
+    resolved_by int REFERENCES Users(id)
+);
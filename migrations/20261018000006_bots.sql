@@ -0,0 +1,8 @@
+-- Accounts the server plays bot seats with. Nobody can log in as them
+ALTER TABLE Users ADD COLUMN bot boolean NOT NULL DEFAULT 0;
+
+INSERT OR IGNORE INTO Users (username, password, balance, bot, display_name, avatar) VALUES
+    ('basic-bot', '!', 5000, 1, 'Basic strategy bot', 'chip'),
+    ('cautious-bot', '!', 5000, 1, 'Never bust bot', 'chip'),
+    ('random-bot', '!', 5000, 1, 'Random bot', 'dice'),
+    ('dealer-bot', '!', 5000, 1, 'Dealer mimic bot', 'crown');
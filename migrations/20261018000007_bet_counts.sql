@@ -0,0 +1,3 @@
+-- The shoe's Hi-Lo count when each opening bet was placed
+ALTER TABLE Ledger ADD COLUMN running_count int;
+ALTER TABLE Ledger ADD COLUMN true_count real;
@@ -0,0 +1,42 @@
+-- Every finished round, with enough to deal it again from the same shoe
+CREATE TABLE Rounds (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    room varchar(6) NOT NULL,
+    rules text NOT NULL,
+    shoe_seed int NOT NULL,
+    shoe_position int NOT NULL,
+    dealer_cards text NOT NULL,
+    training boolean NOT NULL,
+    started_at int NOT NULL,
+    finished_at int NOT NULL
+);
+
+-- The hands still at the table when a round ended
+CREATE TABLE RoundHands (
+    round_id int NOT NULL REFERENCES Rounds(id),
+    hand int NOT NULL,
+    seat int NOT NULL,
+    user_id int NOT NULL REFERENCES Users(id),
+    second_hand boolean NOT NULL,
+    cards text NOT NULL,
+    bet int NOT NULL,
+    result varchar(16) NOT NULL,
+    payout int NOT NULL,
+    PRIMARY KEY (round_id, hand)
+);
+
+-- What players did, in order
+CREATE TABLE RoundActions (
+    round_id int NOT NULL REFERENCES Rounds(id),
+    seq int NOT NULL,
+    hand int NOT NULL,
+    user_id int NOT NULL REFERENCES Users(id),
+    action varchar(8) NOT NULL,
+    amount int,
+    card text,
+    at int NOT NULL,
+    PRIMARY KEY (round_id, seq)
+);
+
+CREATE INDEX RoundHandsByUser ON RoundHands (user_id, round_id);
+CREATE INDEX RoundsByRoom ON Rounds (room, id);
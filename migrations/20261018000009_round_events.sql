@@ -0,0 +1,8 @@
+-- What the table saw during each round, as sent, for replaying it
+CREATE TABLE RoundEvents (
+    round_id int NOT NULL REFERENCES Rounds(id),
+    seq int NOT NULL,
+    after_ms int NOT NULL,
+    action text NOT NULL,
+    PRIMARY KEY (round_id, seq)
+);
@@ -0,0 +1,2 @@
+-- Whether someone appears on the public leaderboards
+ALTER TABLE Users ADD COLUMN on_leaderboards boolean NOT NULL DEFAULT 1;
@@ -0,0 +1,93 @@
+use std::collections::HashSet;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::{data::RoomId, hex};
+
+/// Who can find and join a room
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    /// Listed in the lobby
+    #[default]
+    Public,
+    /// Anyone with the id can join, but it isn't listed
+    Unlisted,
+    /// Needs the room password or an invite link
+    Private,
+}
+
+/// Who may join a room, and how they prove it
+pub struct Access {
+    pub visibility: Visibility,
+    password_hash: Option<String>,
+    /// The user that created the room
+    pub owner: i64,
+    /// Users who have given the password or followed an invite
+    allowed: HashSet<i64>,
+    /// Invite links are signed with this, so replacing it revokes all of them
+    invite_key: [u8; 32],
+}
+
+impl Access {
+    pub fn new(visibility: Visibility, password_hash: Option<String>, owner: i64) -> Self {
+        Self {
+            visibility,
+            password_hash,
+            owner,
+            allowed: HashSet::from([owner]),
+            invite_key: new_key(),
+        }
+    }
+
+    pub fn is_allowed(&self, user_id: i64) -> bool {
+        self.visibility != Visibility::Private || self.allowed.contains(&user_id)
+    }
+
+    pub fn allow(&mut self, user_id: i64) {
+        self.allowed.insert(user_id);
+    }
+
+    pub fn password_hash(&self) -> Option<&str> {
+        self.password_hash.as_deref()
+    }
+
+    fn mac(&self, id: &RoomId) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.invite_key).unwrap();
+        mac.update(id.to_string().as_bytes());
+        mac
+    }
+
+    pub fn invite_token(&self, id: &RoomId) -> String {
+        let tag = self.mac(id).finalize().into_bytes();
+        hex::encode(&tag)
+    }
+
+    pub fn invite_link(&self, id: &RoomId) -> String {
+        format!("/{id}?invite={}", self.invite_token(id))
+    }
+
+    pub fn check_invite(&self, id: &RoomId, token: &str) -> bool {
+        let Some(tag) = hex::decode(token) else {
+            return false;
+        };
+        self.mac(id).verify_slice(&tag).is_ok()
+    }
+
+    /// Invalidates every invite link handed out so far. Anyone who got in
+    /// with one but isn't in `keep` has to be invited again
+    pub fn revoke_invites(&mut self, keep: impl IntoIterator<Item = i64>) {
+        self.invite_key = new_key();
+        self.allowed = keep.into_iter().collect();
+        self.allowed.insert(self.owner);
+    }
+}
+
+fn new_key() -> [u8; 32] {
+    let mut key = [0; 32];
+    OsRng.fill_bytes(&mut key);
+    key
+}
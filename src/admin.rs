@@ -0,0 +1,509 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, Path, Query, State},
+    response::{Html, IntoResponse, Redirect},
+    routing::{get, post},
+    Extension, Form, Json, Router,
+};
+use axum_login::{
+    axum_sessions::extractors::{ReadableSession, WritableSession},
+    RequireAuthorizationLayer,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    access::Visibility,
+    card::Card,
+    count::Count,
+    counters, csrf,
+    data::{Phase, Room, RoomId},
+    ledger::{Entry, LedgerKind},
+    odds::{Calculator, DealerOdds, PlayerEv, Shoe},
+    routes::{error_400, error_403, error_404, TERA},
+    AppState, Role, ServerAction, User, Who,
+};
+
+/// Routes under `/admin`, which only admins may use
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(dashboard))
+        .route("/rooms/:id", get(room))
+        .route("/rooms/:id/odds", get(room_odds))
+        .route("/rooms/:id/close", post(close_room))
+        .route("/rooms/:id/kick", post(kick))
+        .route("/users", get(search_users))
+        .route("/users/:id", get(user))
+        .route("/users/:id/balance", post(adjust_balance))
+        .route("/users/:id/role", post(set_role))
+        .route("/users/:id/ban", post(set_banned))
+        .route_layer(RequireAuthorizationLayer::<i64, User, Role>::login_with_role(Role::Admin..))
+}
+
+#[derive(Serialize)]
+struct RoomSummary {
+    id: String,
+    phase: Phase,
+    visibility: Visibility,
+    seats: usize,
+    max_seats: usize,
+    spectators: usize,
+    locked: bool,
+    total_bet: u64,
+    shoe_remaining: usize,
+    count: Count,
+}
+
+impl RoomSummary {
+    fn new(room: &Room) -> Self {
+        Self {
+            id: room.id.to_string(),
+            phase: room.phase(),
+            visibility: room.access.visibility,
+            seats: room.taken_seats().len(),
+            max_seats: room.rules.max_players,
+            spectators: room.spectators.len(),
+            locked: room.locked,
+            total_bet: room.hands.iter().map(|hand| u64::from(hand.bet)).sum(),
+            shoe_remaining: room.decks.len(),
+            count: room.count(),
+        }
+    }
+}
+
+async fn dashboard(
+    State((state, database)): State<AppState>,
+    mut session: WritableSession,
+) -> impl IntoResponse {
+    let mut rooms = state
+        .lock()
+        .await
+        .rooms
+        .values()
+        .map(RoomSummary::new)
+        .collect::<Vec<_>>();
+    rooms.sort_by(|a, b| a.id.cmp(&b.id));
+    let suspects = counters::suspects(&*database.lock().await).await;
+    let mut context = tera::Context::new();
+    context.insert("rooms", &rooms);
+    context.insert("suspects", &suspects);
+    context.insert("csrf_token", &csrf::token(&mut session));
+    Html(TERA.render("admin/index.html", &context).unwrap())
+}
+
+#[derive(Serialize)]
+struct HandSummary {
+    who: String,
+    username: String,
+    second_hand: bool,
+    bet: u32,
+    cards: Vec<Card>,
+}
+
+async fn room(
+    Path(id): Path<RoomId>,
+    State((state, _database)): State<AppState>,
+    mut session: WritableSession,
+) -> impl IntoResponse {
+    let lock = state.lock().await;
+    let Some(room) = lock.rooms.get(&id) else {
+        return error_404().await.into_response();
+    };
+    let hands = room
+        .hands
+        .iter()
+        .map(|hand| HandSummary {
+            who: hand.who().to_string(),
+            username: room
+                .players
+                .get(hand.who())
+                .map_or_else(String::new, |user| user.username.clone()),
+            second_hand: hand.is_second(),
+            bet: hand.bet,
+            cards: hand.hand.cards.clone(),
+        })
+        .collect::<Vec<_>>();
+    let mut context = tera::Context::new();
+    context.insert("room", &RoomSummary::new(room));
+    context.insert("hands", &hands);
+    context.insert("dealer", &room.dealer_hand);
+    drop(lock);
+    context.insert("csrf_token", &csrf::token(&mut session));
+    Html(TERA.render("admin/room.html", &context).unwrap()).into_response()
+}
+
+#[derive(Serialize)]
+struct HandOdds {
+    seat: usize,
+    cards: Vec<Card>,
+    ev: PlayerEv,
+}
+
+#[derive(Serialize)]
+struct RoomOdds {
+    upcard: Card,
+    unseen: u32,
+    dealer: DealerOdds,
+    hands: Vec<HandOdds>,
+}
+
+/// The exact odds of the round in play, from what a player at the table
+/// could know: the hole card counts as unseen
+async fn room_odds(
+    Path(id): Path<RoomId>,
+    State((state, _database)): State<AppState>,
+) -> impl IntoResponse {
+    let lock = state.lock().await;
+    let Some(room) = lock.rooms.get(&id) else {
+        return error_404().await.into_response();
+    };
+    if room.phase() != Phase::Playing || room.dealer_hand.len() != 2 {
+        return error_400().into_response();
+    }
+    let mut shoe = Shoe::new(&room.decks);
+    shoe.add(room.dealer_hand[0]);
+    let upcard = room.dealer_hand[1];
+    let rules = room.rules.clone();
+    let hands = room
+        .hands
+        .iter()
+        .filter(|hand| hand.hand.cards.len() >= 2)
+        .map(|hand| (hand.seat(), hand.hand.cards.clone()))
+        .collect::<Vec<_>>();
+    drop(lock);
+
+    //Working through every card left can take a moment, so keep it off the runtime
+    let odds = tokio::task::spawn_blocking(move || {
+        let mut calculator = Calculator::new();
+        RoomOdds {
+            upcard,
+            unseen: shoe.len(),
+            dealer: calculator.dealer(&shoe, upcard, &rules),
+            hands: hands
+                .into_iter()
+                .map(|(seat, cards)| HandOdds {
+                    seat,
+                    ev: calculator.player(&shoe, &cards, upcard, &rules),
+                    cards,
+                })
+                .collect(),
+        }
+    })
+    .await
+    .unwrap();
+    Json(odds).into_response()
+}
+
+#[derive(Deserialize)]
+struct CsrfForm {
+    csrf_token: String,
+}
+
+/// Refunds every outstanding bet and sends everyone home
+async fn close_room(
+    Path(id): Path<RoomId>,
+    ConnectInfo(who): ConnectInfo<SocketAddr>,
+    State((state, _database)): State<AppState>,
+    Extension(admin): Extension<User>,
+    session: ReadableSession,
+    Form(form): Form<CsrfForm>,
+) -> impl IntoResponse {
+    if !csrf::verify(&session, &form.csrf_token) {
+        return error_403().into_response();
+    }
+    let Some(mut room) = state.lock().await.remove_room(&id) else {
+        return error_404().await.into_response();
+    };
+    println!("{} ({who}) closed room {id}", admin.username);
+    room.refund_bets(None).await;
+    let players = room.players.keys().copied().collect::<Vec<_>>();
+    for player in players {
+        room.disconnect(player, &ServerAction::RoomClosed).await;
+    }
+    room.dismiss_spectators(&ServerAction::RoomClosed).await;
+    Redirect::to("/admin").into_response()
+}
+
+#[derive(Deserialize)]
+struct KickForm {
+    player: Who,
+    csrf_token: String,
+}
+
+async fn kick(
+    Path(id): Path<RoomId>,
+    ConnectInfo(who): ConnectInfo<SocketAddr>,
+    State((state, _database)): State<AppState>,
+    Extension(admin): Extension<User>,
+    session: ReadableSession,
+    Form(form): Form<KickForm>,
+) -> impl IntoResponse {
+    if !csrf::verify(&session, &form.csrf_token) {
+        return error_403().into_response();
+    }
+    let mut lock = state.lock().await;
+    let Some(room) = lock.rooms.get_mut(&id) else {
+        return error_404().await.into_response();
+    };
+    if !room.sockets.contains(&form.player) {
+        return error_404().await.into_response();
+    }
+    println!(
+        "{} ({who}) kicked {} from room {id}",
+        admin.username, form.player
+    );
+    if room.sockets.len() == 1 {
+        //Nobody would be left, so this is the same as closing the room
+        let mut room = lock.remove_room(&id).unwrap();
+        drop(lock);
+        room.refund_bets(None).await;
+        room.disconnect(form.player, &ServerAction::Kicked).await;
+        room.dismiss_spectators(&ServerAction::RoomClosed).await;
+        return Redirect::to("/admin").into_response();
+    }
+    room.kick(form.player).await;
+    Redirect::to(&format!("/admin/rooms/{id}")).into_response()
+}
+
+#[derive(Deserialize)]
+struct Search {
+    #[serde(default)]
+    q: String,
+}
+
+async fn search_users(
+    Query(search): Query<Search>,
+    State((_state, database)): State<AppState>,
+) -> impl IntoResponse {
+    let pattern = format!("%{}%", search.q.trim());
+    let conn = database.lock().await;
+    let users = sqlx::query_as!(
+        UserSummary,
+        r#"SELECT id AS "id!", username, balance, role AS "role: Role", banned
+        FROM Users
+        WHERE username LIKE ?
+        ORDER BY username
+        LIMIT 50"#,
+        pattern
+    )
+    .fetch_all(&*conn)
+    .await
+    .unwrap();
+    drop(conn);
+    let mut context = tera::Context::new();
+    context.insert("q", &search.q);
+    context.insert("users", &users);
+    Html(TERA.render("admin/users.html", &context).unwrap())
+}
+
+#[derive(Serialize)]
+struct LedgerRow {
+    amount: i64,
+    balance_after: i64,
+    kind: LedgerKind,
+    room: Option<String>,
+    note: Option<String>,
+    actor: Option<String>,
+    true_count: Option<f64>,
+    created_at: i64,
+}
+
+/// Everything about a user that is safe to show on a page
+#[derive(Serialize)]
+struct UserSummary {
+    id: i64,
+    username: String,
+    balance: i64,
+    role: Role,
+    banned: bool,
+}
+
+#[derive(Deserialize)]
+struct UserQuery {
+    /// Set when an adjustment was refused for taking the balance below 0
+    #[serde(default)]
+    overdrawn: bool,
+}
+
+async fn user(
+    Path(id): Path<i64>,
+    Query(query): Query<UserQuery>,
+    State((_state, database)): State<AppState>,
+    mut session: WritableSession,
+) -> impl IntoResponse {
+    let conn = database.lock().await;
+    let user = sqlx::query_as!(
+        UserSummary,
+        r#"SELECT id AS "id!", username, balance, role AS "role: Role", banned
+        FROM Users
+        WHERE id = ?"#,
+        id
+    )
+    .fetch_optional(&*conn)
+    .await
+    .unwrap();
+    let Some(user) = user else {
+        return error_404().await.into_response();
+    };
+    let ledger = sqlx::query_as!(
+        LedgerRow,
+        r#"SELECT Ledger.amount AS "amount!", Ledger.balance_after AS "balance_after!",
+            Ledger.kind AS "kind!: LedgerKind", Ledger.room, Ledger.note,
+            Actors.username AS "actor?", Ledger.true_count, Ledger.created_at AS "created_at!"
+        FROM Ledger
+        LEFT JOIN Users AS Actors ON Actors.id = Ledger.actor_id
+        WHERE Ledger.user_id = ?
+        ORDER BY Ledger.id DESC
+        LIMIT 50"#,
+        id
+    )
+    .fetch_all(&*conn)
+    .await
+    .unwrap();
+    let rounds = sqlx::query!(
+        r#"SELECT DISTINCT Rounds.id AS "id!", Rounds.room, Rounds.finished_at
+        FROM Rounds
+        JOIN RoundHands ON RoundHands.round_id = Rounds.id
+        WHERE RoundHands.user_id = ?
+        ORDER BY Rounds.id DESC
+        LIMIT 20"#,
+        id
+    )
+    .fetch_all(&*conn)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|round| (round.id, round.room, round.finished_at))
+    .collect::<Vec<_>>();
+    drop(conn);
+    let mut context = tera::Context::new();
+    context.insert("user", &user);
+    context.insert("ledger", &ledger);
+    context.insert("rounds", &rounds);
+    context.insert("overdrawn", &query.overdrawn);
+    context.insert("csrf_token", &csrf::token(&mut session));
+    Html(TERA.render("admin/user.html", &context).unwrap()).into_response()
+}
+
+#[derive(Deserialize)]
+struct RoleForm {
+    role: Role,
+    csrf_token: String,
+}
+
+async fn set_role(
+    Path(id): Path<i64>,
+    ConnectInfo(who): ConnectInfo<SocketAddr>,
+    State((_state, database)): State<AppState>,
+    Extension(admin): Extension<User>,
+    session: ReadableSession,
+    Form(form): Form<RoleForm>,
+) -> impl IntoResponse {
+    if !csrf::verify(&session, &form.csrf_token) || admin.id == id {
+        println!("{} ({who}) made a bad role change", admin.username);
+        return error_403().into_response();
+    }
+    println!(
+        "{} ({who}) is changing the role of user {id} to {:?}",
+        admin.username, form.role
+    );
+    let conn = database.lock().await;
+    sqlx::query!(
+        "UPDATE Users
+        SET role = ?
+        WHERE id = ?",
+        form.role,
+        id
+    )
+    .execute(&*conn)
+    .await
+    .unwrap();
+    Redirect::to(&format!("/admin/users/{id}")).into_response()
+}
+
+#[derive(Deserialize)]
+struct BanForm {
+    banned: bool,
+    csrf_token: String,
+}
+
+async fn set_banned(
+    Path(id): Path<i64>,
+    ConnectInfo(who): ConnectInfo<SocketAddr>,
+    State((_state, database)): State<AppState>,
+    Extension(admin): Extension<User>,
+    session: ReadableSession,
+    Form(form): Form<BanForm>,
+) -> impl IntoResponse {
+    if !csrf::verify(&session, &form.csrf_token) || admin.id == id {
+        println!("{} ({who}) made a bad ban request", admin.username);
+        return error_403().into_response();
+    }
+    println!(
+        "{} ({who}) set the banned status of user {id} to {}",
+        admin.username, form.banned
+    );
+    let conn = database.lock().await;
+    sqlx::query!(
+        "UPDATE Users
+        SET banned = ?
+        WHERE id = ?",
+        form.banned,
+        id
+    )
+    .execute(&*conn)
+    .await
+    .unwrap();
+    Redirect::to(&format!("/admin/users/{id}")).into_response()
+}
+
+#[derive(Deserialize)]
+struct AdjustForm {
+    amount: i64,
+    reason: String,
+    csrf_token: String,
+}
+
+async fn adjust_balance(
+    Path(id): Path<i64>,
+    ConnectInfo(who): ConnectInfo<SocketAddr>,
+    State((_state, database)): State<AppState>,
+    Extension(admin): Extension<User>,
+    session: ReadableSession,
+    Form(form): Form<AdjustForm>,
+) -> impl IntoResponse {
+    if !csrf::verify(&session, &form.csrf_token) {
+        return error_403().into_response();
+    }
+    let reason = form.reason.trim();
+    if form.amount == 0 || reason.is_empty() {
+        //Every adjustment needs to be explained in the ledger
+        return Redirect::to(&format!("/admin/users/{id}")).into_response();
+    }
+    let conn = database.lock().await;
+    let exists = sqlx::query_scalar!("SELECT id FROM Users WHERE id = ?", id)
+        .fetch_optional(&*conn)
+        .await
+        .unwrap()
+        .is_some();
+    if !exists {
+        return error_404().await.into_response();
+    }
+    let Some(balance) = Entry::new(id, form.amount, LedgerKind::Adjustment)
+        .note(reason)
+        .actor(admin.id)
+        .apply_unless_overdrawn(&conn)
+        .await
+    else {
+        println!(
+            "{} ({who}) tried to take the balance of user {id} below 0",
+            admin.username
+        );
+        return Redirect::to(&format!("/admin/users/{id}?overdrawn=true")).into_response();
+    };
+    println!(
+        "{} ({who}) adjusted the balance of user {id} by {} to {balance}: {reason}",
+        admin.username, form.amount
+    );
+    Redirect::to(&format!("/admin/users/{id}")).into_response()
+}
@@ -0,0 +1,222 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use nutype::nutype;
+use sqlx::SqlitePool;
+
+use crate::data::unix_time;
+use std::net::IpAddr;
+
+/// The outcome of checking a password against what is stored in `Users.password`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verified {
+    /// The password matched an Argon2 hash
+    Valid,
+    /// The password matched, but the row still holds a plaintext password and
+    /// should be rehashed
+    NeedsUpgrade,
+    Invalid,
+}
+
+pub fn hash_password(password: &str) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .unwrap()
+        .to_string()
+}
+
+pub fn verify_password(stored: &str, password: &str) -> Verified {
+    match PasswordHash::new(stored) {
+        Ok(hash) => {
+            if Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+            {
+                Verified::Valid
+            } else {
+                Verified::Invalid
+            }
+        }
+        //Rows created before hashing was introduced hold the password itself
+        Err(_) if constant_time_eq(stored.as_bytes(), password.as_bytes()) => {
+            Verified::NeedsUpgrade
+        }
+        Err(_) => Verified::Invalid,
+    }
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Every new account starts with this much to bet with
+pub const STARTING_BALANCE: i64 = 5000;
+
+#[nutype(
+    sanitize(trim)
+    validate(
+        min_len = 3,
+        max_len = 20,
+        with = |s: &str| {
+            s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        }
+    )
+)]
+#[derive(Deserialize, Serialize, *)]
+pub struct Username(String);
+
+impl std::fmt::Display for Username {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.clone().into_inner())
+    }
+}
+
+pub fn username_error(error: UsernameError) -> &'static str {
+    match error {
+        UsernameError::TooShort => "Usernames must be at least 3 characters long",
+        UsernameError::TooLong => "Usernames can be at most 20 characters long",
+        UsernameError::Invalid => "Usernames may only contain letters, numbers, '_' and '-'",
+    }
+}
+
+/// Checks a new password against the strength rules, returning the first rule it breaks
+pub fn check_password_strength(username: &Username, password: &str) -> Result<(), &'static str> {
+    if password.chars().count() < 8 {
+        return Err("Passwords must be at least 8 characters long");
+    }
+    if password.chars().count() > 128 {
+        return Err("Passwords can be at most 128 characters long");
+    }
+    if !password.chars().any(char::is_alphabetic) || !password.chars().any(|c| c.is_ascii_digit()) {
+        return Err("Passwords must contain both letters and numbers");
+    }
+    if password.eq_ignore_ascii_case(&username.clone().into_inner()) {
+        return Err("Passwords can't be the same as your username");
+    }
+    Ok(())
+}
+
+/// What a row of `LoginAttempts` counts failures against
+pub enum AttemptKey<'a> {
+    Username(&'a str),
+    Ip(IpAddr),
+}
+
+impl AttemptKey<'_> {
+    fn key(&self) -> String {
+        match self {
+            //Usernames are case insensitive
+            AttemptKey::Username(name) => format!("user:{}", name.to_lowercase()),
+            AttemptKey::Ip(ip) => format!("ip:{ip}"),
+        }
+    }
+
+    /// How many failures are allowed before lockouts start. A single address
+    /// may reasonably be shared by several people, so it gets more leeway
+    fn free_attempts(&self) -> i64 {
+        match self {
+            AttemptKey::Username(_) => 5,
+            AttemptKey::Ip(_) => 20,
+        }
+    }
+}
+
+const BASE_LOCKOUT_SECS: i64 = 30;
+const MAX_LOCKOUT_SECS: i64 = 60 * 60;
+/// Failures older than this are forgotten
+const ATTEMPT_WINDOW_SECS: i64 = 24 * 60 * 60;
+
+/// Returns how many seconds are left on the lockout for `key`, if it is locked out
+pub async fn lockout_remaining(conn: &SqlitePool, key: &AttemptKey<'_>) -> Option<i64> {
+    let key = key.key();
+    let locked_until = sqlx::query_scalar!(
+        "SELECT locked_until FROM LoginAttempts
+        WHERE key = ?",
+        key
+    )
+    .fetch_optional(conn)
+    .await
+    .unwrap()?;
+    let remaining = locked_until - unix_time();
+    (remaining > 0).then_some(remaining)
+}
+
+/// Records a failed attempt. Past the free attempts, every further failure
+/// doubles the lockout, up to an hour
+pub async fn record_failure(conn: &SqlitePool, key: &AttemptKey<'_>) {
+    let now = unix_time();
+    let free_attempts = key.free_attempts();
+    let key = key.key();
+    let previous = sqlx::query!(
+        "SELECT failures, last_failure FROM LoginAttempts
+        WHERE key = ?",
+        key
+    )
+    .fetch_optional(conn)
+    .await
+    .unwrap();
+    let failures = match previous {
+        Some(row) if now - row.last_failure < ATTEMPT_WINDOW_SECS => row.failures + 1,
+        _ => 1,
+    };
+    let locked_until = if failures > free_attempts {
+        let doublings = u32::try_from(failures - free_attempts - 1).unwrap_or(u32::MAX);
+        let lockout = 2_i64
+            .checked_pow(doublings)
+            .and_then(|x| x.checked_mul(BASE_LOCKOUT_SECS))
+            .map_or(MAX_LOCKOUT_SECS, |x| x.min(MAX_LOCKOUT_SECS));
+        now + lockout
+    } else {
+        0
+    };
+    sqlx::query!(
+        "INSERT INTO LoginAttempts (key, failures, last_failure, locked_until)
+        VALUES (?, ?, ?, ?)
+        ON CONFLICT(key) DO UPDATE
+        SET failures = excluded.failures,
+            last_failure = excluded.last_failure,
+            locked_until = excluded.locked_until",
+        key,
+        failures,
+        now,
+        locked_until
+    )
+    .execute(conn)
+    .await
+    .unwrap();
+}
+
+pub async fn clear_failures(conn: &SqlitePool, key: &AttemptKey<'_>) {
+    let key = key.key();
+    sqlx::query!(
+        "DELETE FROM LoginAttempts
+        WHERE key = ?",
+        key
+    )
+    .execute(conn)
+    .await
+    .unwrap();
+}
+
+/// Makes the account named by `ADMIN_USERNAME` an admin, so a new server has
+/// someone who can hand out roles. It has to be registered first
+pub async fn bootstrap_admin(conn: &SqlitePool) {
+    let Ok(username) = std::env::var("ADMIN_USERNAME") else {
+        return;
+    };
+    let promoted = sqlx::query!(
+        "UPDATE Users SET role = 'admin' WHERE username = ? AND NOT bot",
+        username
+    )
+    .execute(conn)
+    .await
+    .unwrap()
+    .rows_affected();
+    if promoted == 0 {
+        println!("ADMIN_USERNAME is {username}, but there is no such account to make an admin");
+    } else {
+        println!("{username} is an admin");
+    }
+}
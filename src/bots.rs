@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use enum_iterator::Sequence;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::{
+    data::{MyState, Phase, RoomId},
+    deal, end_turn, place_bet, split,
+    strategy::{BasicStrategy, DealerMimic, Decision, NeverBust, Random, Strategy},
+};
+
+/// The strategies a host can pick from when adding a bot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Sequence)]
+#[serde(rename_all = "snake_case")]
+pub enum BotKind {
+    BasicStrategy,
+    NeverBust,
+    Random,
+    DealerMimic,
+}
+
+impl BotKind {
+    pub fn strategy(self) -> &'static dyn Strategy {
+        match self {
+            BotKind::BasicStrategy => &BasicStrategy,
+            BotKind::NeverBust => &NeverBust,
+            BotKind::Random => &Random,
+            BotKind::DealerMimic => &DealerMimic,
+        }
+    }
+
+    /// The account every bot of this kind bets from
+    pub fn username(self) -> &'static str {
+        match self {
+            BotKind::BasicStrategy => "basic-bot",
+            BotKind::NeverBust => "cautious-bot",
+            BotKind::Random => "random-bot",
+            BotKind::DealerMimic => "dealer-bot",
+        }
+    }
+}
+
+/// What a bot is about to do on its turn
+enum Move {
+    /// How much, and the least the table takes if that's too much
+    Bet(u32, u32),
+    Play(Decision),
+}
+
+/// Plays every bot whose turn it is, until it's someone else's or the round
+/// is over. Bots go through the same actions as everyone else, which check
+/// it really is their turn
+pub async fn play(state: &Arc<Mutex<MyState>>, id: &RoomId) {
+    loop {
+        let (who, user, next) = {
+            let lock = state.lock().await;
+            let Some(room) = lock.rooms.get(id) else {
+                return;
+            };
+            if room.phase() != Phase::Playing || room.current_id().is_none() {
+                return;
+            }
+            let current = room.current();
+            let who = *current.who();
+            let Some(kind) = room.bots.get(&who) else {
+                return;
+            };
+            let strategy = kind.strategy();
+            let hand = &current.hand;
+            let next = if current.bet == 0 {
+                Move::Bet(strategy.bet(&room.rules), room.rules.min_bet)
+            //Split aces only get one card each
+            } else if hand.is_split_aces() || hand.cards.len() == 10 || hand.score().total() >= 21 {
+                Move::Play(Decision::Stand)
+            } else {
+                Move::Play(strategy.decide(hand, room.dealer_hand[1], &room.rules))
+            };
+            (who, room.players[&who].clone(), next)
+        };
+        match next {
+            Move::Bet(amount, min_bet) => {
+                let placed = place_bet(state, id, who, &user, amount).await
+                    || (amount != min_bet && place_bet(state, id, who, &user, min_bet).await);
+                if !placed {
+                    println!(
+                        "{} can't cover its bet, so it's leaving {id}",
+                        user.username
+                    );
+                    let mut lock = state.lock().await;
+                    let Some(room) = lock.rooms.get_mut(id) else {
+                        return;
+                    };
+                    room.kick(who).await;
+                }
+            }
+            Move::Play(Decision::Hit) => deal(state, id, who).await,
+            Move::Play(Decision::Stand) => end_turn(state, id, who).await,
+            Move::Play(Decision::Split) => {
+                if !split(state, id, who, user.id).await {
+                    deal(state, id, who).await;
+                }
+            }
+        }
+    }
+}
@@ -37,14 +37,17 @@ impl Card {
         all::<Card>().collect::<Vec<_>>().try_into().unwrap()
     }
 
-    pub fn decks() -> [Card; 52 * 8] {
-        [Card::one_deck(); 8].concat().try_into().unwrap()
+    /// A shuffled shoe of any number of decks
+    pub fn shuffled_shoe(decks: u8) -> Vec<Card> {
+        Card::seeded_shoe(decks, fastrand::u64(..))
     }
 
-    pub fn shuffled_decks() -> [Card; 52 * 8] {
-        let mut decks = Card::decks();
-        fastrand::shuffle(&mut decks);
-        decks
+    /// The same shoe every time for the same `seed`, so a round can be dealt
+    /// again from its record
+    pub fn seeded_shoe(decks: u8, seed: u64) -> Vec<Card> {
+        let mut shoe = Card::one_deck().repeat(decks.into());
+        fastrand::Rng::with_seed(seed).shuffle(&mut shoe);
+        shoe
     }
     pub fn score_card(&self) -> u8 {
         match self.rank {
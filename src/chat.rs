@@ -0,0 +1,208 @@
+use std::sync::Arc;
+
+use serde::Serialize;
+use sqlx::SqlitePool;
+use tokio::sync::Mutex;
+
+use crate::{
+    data::{unix_time, MyState, RoomId},
+    profile::PlayerInfo,
+    Role, ServerAction, User, Who,
+};
+
+/// Longest message, in characters, after trimming
+pub const MAX_LENGTH: usize = 300;
+/// How many messages a room keeps to show people who sit down later
+pub const HISTORY: usize = 50;
+/// The longest a moderator can mute someone from the table, in minutes
+pub const MAX_MUTE_MINUTES: u32 = 7 * 24 * 60;
+
+/// Words that are masked out of messages, matched as whole words in any case
+const BLOCKED_WORDS: &[&str] = &[
+    "fuck", "fucking", "shit", "cunt", "bitch", "bastard", "asshole", "dick", "wanker", "slut",
+    "whore", "retard", "faggot", "nigger",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChatMessage {
+    pub id: u64,
+    #[serde(skip)]
+    pub user_id: i64,
+    pub from: PlayerInfo,
+    pub text: String,
+    pub at: i64,
+}
+
+impl ChatMessage {
+    pub fn action(&self) -> ServerAction {
+        ServerAction::Chat {
+            id: self.id,
+            from: self.from.clone(),
+            text: self.text.clone(),
+            at: self.at,
+        }
+    }
+}
+
+/// Checks a message is fit to post, and masks any blocked words in it
+pub fn clean(text: &str) -> Result<String, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("Messages can't be empty".to_string());
+    }
+    if text.chars().count() > MAX_LENGTH {
+        return Err(format!("Messages can be at most {MAX_LENGTH} characters"));
+    }
+    let text = text.replace(char::is_control, " ");
+    let mut cleaned = String::with_capacity(text.len());
+    let mut word = String::new();
+    for c in text.chars().chain(std::iter::once(' ')) {
+        if c.is_alphanumeric() {
+            word.push(c);
+            continue;
+        }
+        if BLOCKED_WORDS.contains(&word.to_lowercase().as_str()) {
+            cleaned.extend(word.chars().map(|_| '*'));
+        } else {
+            cleaned.push_str(&word);
+        }
+        word.clear();
+        cleaned.push(c);
+    }
+    cleaned.pop();
+    Ok(cleaned)
+}
+
+async fn muted_until(database: &Arc<Mutex<SqlitePool>>, user_id: i64) -> Option<i64> {
+    let conn = database.lock().await;
+    sqlx::query_scalar!("SELECT muted_until FROM Users WHERE id = ?", user_id)
+        .fetch_one(&*conn)
+        .await
+        .unwrap()
+        .filter(|until| *until > unix_time())
+}
+
+/// Posts `text` to everyone in the room, if `user` is allowed to
+pub async fn send(state: &Arc<Mutex<MyState>>, id: &RoomId, who: Who, user: &User, text: &str) {
+    let Some(database) = state.lock().await.rooms.get(id).map(|room| room.database()) else {
+        return;
+    };
+    let reason = if muted_until(&database, user.id).await.is_some() {
+        Some("You have been muted".to_string())
+    } else if !state.lock().await.chat_limits.try_acquire(user.id) {
+        Some("You're sending messages too quickly".to_string())
+    } else {
+        None
+    };
+    let text = match reason.map_or_else(|| clean(text), Err) {
+        Ok(text) => text,
+        Err(reason) => {
+            println!("{} ({who}) can't chat in {id}: {reason}", user.username);
+            let action = ServerAction::ChatRejected { reason };
+            if let Some(room) = state.lock().await.rooms.get_mut(id) {
+                room.notify_player(who, &action).await;
+            }
+            return;
+        }
+    };
+    let mut lock = state.lock().await;
+    let Some(room) = lock.rooms.get_mut(id) else {
+        return;
+    };
+    let message = room.post_chat(user, text);
+    room.notify_all(&message.action()).await;
+}
+
+/// Flags a message from the room's history for moderators to look at
+pub async fn report(state: &Arc<Mutex<MyState>>, id: &RoomId, who: Who, user: &User, message: u64) {
+    let mut lock = state.lock().await;
+    let Some(room) = lock.rooms.get_mut(id) else {
+        return;
+    };
+    let Some(reported) = room.chat.iter().find(|m| m.id == message).cloned() else {
+        println!(
+            "{} ({who}) reported a message that isn't in {id}",
+            user.username
+        );
+        return;
+    };
+    if reported.user_id == user.id {
+        return;
+    }
+    let database = room.database();
+    let conn = database.lock().await;
+    let room_id = id.to_string();
+    let now = unix_time();
+    sqlx::query!(
+        "INSERT INTO ChatReports (room, reporter_id, reported_id, text, sent_at, created_at)
+        VALUES (?, ?, ?, ?, ?, ?)",
+        room_id,
+        user.id,
+        reported.user_id,
+        reported.text,
+        reported.at,
+        now
+    )
+    .execute(&*conn)
+    .await
+    .unwrap();
+    drop(conn);
+    println!(
+        "{} ({who}) reported {}'s message in {id}",
+        user.username, reported.from.username
+    );
+    room.notify_player(who, &ServerAction::ChatReported { id: message })
+        .await;
+}
+
+/// Stops `user_id` chatting anywhere for `minutes`, or lets them chat again
+/// if it's 0
+pub async fn mute(database: &Arc<Mutex<SqlitePool>>, user_id: i64, minutes: u32) {
+    let until = (minutes > 0).then(|| unix_time() + 60 * i64::from(minutes.min(MAX_MUTE_MINUTES)));
+    let conn = database.lock().await;
+    sqlx::query!(
+        "UPDATE Users SET muted_until = ? WHERE id = ?",
+        until,
+        user_id
+    )
+    .execute(&*conn)
+    .await
+    .unwrap();
+}
+
+/// Lets a moderator sitting at the table mute whoever sits in `seat`
+pub async fn mute_seat(
+    state: &Arc<Mutex<MyState>>,
+    id: &RoomId,
+    who: Who,
+    user: &User,
+    seat: usize,
+    minutes: u32,
+) {
+    if user.role < Role::Moderator {
+        println!("{} ({who}) tried to mute someone in {id}", user.username);
+        return;
+    }
+    let lock = state.lock().await;
+    let Some(room) = lock.rooms.get(id) else {
+        return;
+    };
+    let Some(target) = room
+        .seated_at(seat)
+        .and_then(|target| room.players.get(&target))
+    else {
+        return;
+    };
+    let (target_id, target_name) = (target.id, target.username.clone());
+    let database = room.database();
+    drop(lock);
+    mute(&database, target_id, minutes).await;
+    if minutes == 0 {
+        println!("{} ({who}) unmuted {target_name} from {id}", user.username);
+    } else {
+        println!(
+            "{} ({who}) muted {target_name} for {minutes} minutes from {id}",
+            user.username
+        );
+    }
+}
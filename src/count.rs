@@ -0,0 +1,88 @@
+//! Hi-Lo card counting, so the house can tell when someone bets with the count
+
+use serde::Serialize;
+
+use crate::card::Card;
+
+/// What a card adds to the running count once it's seen
+pub fn hi_lo(card: Card) -> i32 {
+    match card.score_card() {
+        2..=6 => 1,
+        7..=9 => 0,
+        _ => -1,
+    }
+}
+
+/// The count of a shoe, from everything that has been seen of it so far
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Count {
+    pub running: i32,
+    /// The running count per deck left to be seen
+    pub true_count: f64,
+}
+
+impl Count {
+    /// Counts a shoe that still holds `unseen`: the cards left in it plus
+    /// any dealt face down. A whole shoe counts to zero, so the cards already
+    /// seen count to the opposite of those
+    pub fn of<'a>(unseen: impl IntoIterator<Item = &'a Card>) -> Self {
+        let (running, left) = unseen.into_iter().fold((0, 0), |(running, left), &card| {
+            (running - hi_lo(card), left + 1)
+        });
+        //Never divide by less than half a deck, so the last few cards can't blow it up
+        let decks_left = (f64::from(left) / 52.0).max(0.5);
+        Self {
+            running,
+            true_count: f64::from(running) / decks_left,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::Rank;
+
+    #[test]
+    fn low_cards_count_up_and_high_cards_down() {
+        let deck = Card::one_deck();
+        let value = |rank| hi_lo(*deck.iter().find(|card| card.rank == rank).unwrap());
+        assert_eq!(value(Rank::Two), 1);
+        assert_eq!(value(Rank::Six), 1);
+        assert_eq!(value(Rank::Seven), 0);
+        assert_eq!(value(Rank::Nine), 0);
+        assert_eq!(value(Rank::Ten), -1);
+        assert_eq!(value(Rank::King), -1);
+        assert_eq!(value(Rank::Ace), -1);
+        assert_eq!(deck.iter().copied().map(hi_lo).sum::<i32>(), 0);
+    }
+
+    #[test]
+    fn seeing_low_cards_makes_the_count_positive() {
+        let shoe = Card::one_deck().repeat(2);
+        assert_eq!(Count::of(&shoe).running, 0);
+        //Every 2 through 6 has been seen, leaving the rest unseen
+        let unseen = shoe
+            .iter()
+            .filter(|card| hi_lo(**card) != 1)
+            .collect::<Vec<_>>();
+        let count = Count::of(unseen.iter().copied());
+        assert_eq!(count.running, 40);
+        assert!((count.true_count - 40.0 / (64.0 / 52.0)).abs() < 1e-9);
+        //And seeing high cards makes it negative
+        let unseen = shoe.iter().filter(|card| hi_lo(**card) != -1);
+        assert_eq!(Count::of(unseen).running, -40);
+    }
+
+    #[test]
+    fn true_count_never_divides_by_less_than_half_a_deck() {
+        let tens = Card::one_deck()
+            .into_iter()
+            .filter(|card| card.score_card() == 10)
+            .take(4)
+            .collect::<Vec<_>>();
+        let count = Count::of(&tens);
+        assert_eq!(count.running, 4);
+        assert_eq!(count.true_count, 8.0);
+    }
+}
@@ -0,0 +1,144 @@
+//! Spotting players whose bets follow the count
+
+use serde::Serialize;
+use sqlx::SqlitePool;
+
+use crate::data::unix_time;
+
+/// How far back to look at someone's bets
+const WINDOW: i64 = 30 * 24 * 60 * 60;
+/// Too few bets and a correlation means nothing
+const MIN_BETS: i64 = 50;
+/// How closely bets have to follow the true count to be flagged
+const THRESHOLD: f64 = 0.3;
+
+/// Someone who bets more when the count is in their favour
+#[derive(Serialize)]
+pub struct Suspect {
+    pub id: i64,
+    pub username: String,
+    pub bets: i64,
+    /// Between the true count and the size of their opening bets
+    pub correlation: f64,
+    /// Their average bet when the count favours the house
+    pub low_count_bet: Option<f64>,
+    /// And when it favours them
+    pub high_count_bet: Option<f64>,
+}
+
+/// Everyone whose recent bets correlate with the true count, most
+/// suspicious first
+pub async fn suspects(conn: &SqlitePool) -> Vec<Suspect> {
+    let since = unix_time() - WINDOW;
+    let rows = sqlx::query!(
+        r#"SELECT Users.id AS "id!", Users.username,
+            COUNT(*) AS "bets!: i64",
+            SUM(Ledger.true_count) AS "x!: f64",
+            SUM(-Ledger.amount) AS "y!: f64",
+            SUM(Ledger.true_count * Ledger.true_count) AS "xx!: f64",
+            SUM(CAST(Ledger.amount AS real) * Ledger.amount) AS "yy!: f64",
+            SUM(Ledger.true_count * -Ledger.amount) AS "xy!: f64",
+            AVG(CASE WHEN Ledger.true_count <= 0 THEN -Ledger.amount END) AS "low_count_bet: f64",
+            AVG(CASE WHEN Ledger.true_count >= 2 THEN -Ledger.amount END) AS "high_count_bet: f64"
+        FROM Ledger
+        JOIN Users ON Users.id = Ledger.user_id
+        WHERE Ledger.kind = 'bet' AND Ledger.true_count IS NOT NULL
+            AND Ledger.created_at >= ? AND NOT Users.bot
+        GROUP BY Users.id
+        HAVING COUNT(*) >= ?"#,
+        since,
+        MIN_BETS
+    )
+    .fetch_all(conn)
+    .await
+    .unwrap();
+
+    let mut suspects = rows
+        .into_iter()
+        .filter_map(|row| {
+            let n = row.bets as f64;
+            let spread = (n * row.xx - row.x * row.x) * (n * row.yy - row.y * row.y);
+            //Someone who always bets the same can't be betting with the count
+            if spread <= 0.0 {
+                return None;
+            }
+            let correlation = (n * row.xy - row.x * row.y) / spread.sqrt();
+            (correlation >= THRESHOLD).then_some(Suspect {
+                id: row.id,
+                username: row.username,
+                bets: row.bets,
+                correlation,
+                low_count_bet: row.low_count_bet,
+                high_count_bet: row.high_count_bet,
+            })
+        })
+        .collect::<Vec<_>>();
+    suspects.sort_by(|a, b| b.correlation.total_cmp(&a.correlation));
+    suspects
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+
+    /// Records `bets` opening bets for a new user, sized by `size` from each
+    /// bet's true count
+    async fn bettor(conn: &SqlitePool, name: &str, bot: bool, bets: i64, size: fn(f64) -> i64) {
+        let id = sqlx::query_scalar::<_, i64>(
+            "INSERT INTO Users (username, password, balance, bot) VALUES (?, '!', 5000, ?)
+            RETURNING id",
+        )
+        .bind(name)
+        .bind(bot)
+        .fetch_one(conn)
+        .await
+        .unwrap();
+        for n in 0..bets {
+            //True counts from -3 to 4, over and over
+            let true_count = (n % 8 - 3) as f64;
+            sqlx::query(
+                "INSERT INTO Ledger (user_id, amount, balance_after, kind, running_count,
+                    true_count, created_at)
+                VALUES (?, ?, 5000, 'bet', 0, ?, ?)",
+            )
+            .bind(id)
+            .bind(-size(true_count))
+            .bind(true_count)
+            .bind(unix_time())
+            .execute(conn)
+            .await
+            .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn flags_only_those_betting_with_the_count() {
+        //Every connection to an in-memory database gets a database of its own
+        let conn = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!().run(&conn).await.unwrap();
+        let spread = |true_count: f64| if true_count >= 2.0 { 100 } else { 10 };
+        bettor(&conn, "counter", false, 60, spread).await;
+        bettor(&conn, "flat", false, 60, |_| 25).await;
+        bettor(&conn, "backwards", false, 60, |tc| 50 - 10 * tc as i64).await;
+        bettor(&conn, "newcomer", false, 49, spread).await;
+        bettor(&conn, "counting-bot", true, 60, spread).await;
+
+        let suspects = suspects(&conn).await;
+        let names = suspects
+            .iter()
+            .map(|s| s.username.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["counter"]);
+        let counter = &suspects[0];
+        assert_eq!(counter.bets, 60);
+        assert!(counter.correlation > 0.8, "{}", counter.correlation);
+        assert_eq!(counter.low_count_bet, Some(10.0));
+        assert_eq!(counter.high_count_bet, Some(100.0));
+    }
+}
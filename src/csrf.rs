@@ -0,0 +1,41 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use axum::http::{header, HeaderMap};
+use axum_login::axum_sessions::extractors::{ReadableSession, WritableSession};
+
+use crate::{auth::constant_time_eq, hex};
+
+const SESSION_KEY: &str = "csrf_token";
+
+/// Returns the session's CSRF token, creating one if it doesn't have one yet.
+/// Every form that changes state must submit it back in a `csrf_token` field
+pub fn token(session: &mut WritableSession) -> String {
+    if let Some(token) = session.get::<String>(SESSION_KEY) {
+        return token;
+    }
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let token = hex::encode(&bytes);
+    session.insert(SESSION_KEY, &token).unwrap();
+    token
+}
+
+pub fn verify(session: &ReadableSession, submitted: &str) -> bool {
+    match session.get::<String>(SESSION_KEY) {
+        Some(token) => constant_time_eq(token.as_bytes(), submitted.as_bytes()),
+        None => false,
+    }
+}
+
+/// Checks that a request was made by one of our own pages, by comparing the
+/// `Origin` the browser attached against the `Host` it was sent to
+pub fn same_origin(headers: &HeaderMap) -> bool {
+    let origin = headers.get(header::ORIGIN).and_then(|o| o.to_str().ok());
+    let host = headers.get(header::HOST).and_then(|h| h.to_str().ok());
+    let (Some(origin), Some(host)) = (origin, host) else {
+        return false;
+    };
+    let origin = origin
+        .strip_prefix("http://")
+        .or_else(|| origin.strip_prefix("https://"));
+    origin == Some(host)
+}
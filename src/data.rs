@@ -1,6 +1,12 @@
+use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
-use std::{collections::HashMap, net::SocketAddr, sync::Arc};
-use tokio::sync::Mutex;
+use std::{
+    collections::{HashMap, VecDeque},
+    net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::{broadcast, Mutex};
 
 use axum::extract::ws::Message;
 use futures::SinkExt;
@@ -8,10 +14,31 @@ use nanoid::nanoid;
 use nutype::nutype;
 
 use crate::{
-    card::{Card, Rank},
-    ServerAction, Socket, Who,
+    access::{Access, Visibility},
+    bots::BotKind,
+    card::Card,
+    chat::{self, ChatMessage},
+    count::Count,
+    engine::{self, Score, TableRules},
+    history::{Move, RoundLog},
+    ledger::Entry,
+    limit::Buckets,
+    lobby::{LobbyEvent, RoomListing},
+    profile::PlayerInfo,
+    strategy::Decision,
+    training::Training,
+    ServerAction, Socket, User, Who,
 };
 
+pub fn unix_time() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        .try_into()
+        .unwrap()
+}
+
 pub struct Sockets(pub HashMap<Who, Socket>);
 
 impl Sockets {
@@ -37,96 +64,540 @@ impl Sockets {
     pub fn remove(&mut self, who: &Who) -> Option<Socket> {
         self.0.remove(who)
     }
+
+    pub fn contains(&self, who: &Who) -> bool {
+        self.0.contains_key(who)
+    }
+
+    /// Like `notify`, but for connections the game doesn't depend on, so one
+    /// that has gone away is skipped rather than treated as an error
+    pub async fn broadcast(&mut self, action: &ServerAction) {
+        let msg = serde_json::to_string(action).unwrap();
+        for socket in self.0.values_mut() {
+            let _ = socket.send(Message::Text(msg.clone())).await;
+        }
+    }
+
+    pub async fn broadcast_close(&mut self) {
+        for socket in self.0.values_mut() {
+            let _ = socket.send(Message::Close(None)).await;
+        }
+    }
+}
+
+/// Where a room is in its round
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Phase {
+    Waiting,
+    Playing,
+    Finished,
 }
 
 pub struct Room {
+    pub id: RoomId,
+    pub rules: TableRules,
+    pub access: Access,
     pub started: bool,
-    current_hand: usize,
+    pub finished: bool,
+    /// The seat that runs the table, if anyone is sitting down
+    pub host: Option<Who>,
+    /// Whether the host has closed the table to new players
+    pub locked: bool,
+    /// The hand whose turn it is, while a round is being played
+    current: Option<HandId>,
+    next_hand_id: u32,
     pub dealer_hand: Vec<Card>,
     pub hands: Vec<Hand>,
     pub sockets: Sockets,
+    pub players: HashMap<Who, User>,
+    /// Connections watching the table without a seat
+    pub spectators: Sockets,
+    /// Seats the server plays itself, and how
+    pub bots: HashMap<Who, BotKind>,
+    next_bot: u16,
+    /// The last few chat messages, oldest first
+    pub chat: VecDeque<ChatMessage>,
+    next_chat_id: u64,
+    /// Set for solo practice tables, which are played with play money
+    pub training: Option<Training>,
     pub decks: Vec<Card>,
+    /// What the shoe was shuffled from, so it can be dealt again
+    shoe_seed: u64,
+    /// What has happened in the round being played, to save once it's over
+    pub round: Option<RoundLog>,
     db: Arc<Mutex<SqlitePool>>,
+    lobby: broadcast::Sender<LobbyEvent>,
 }
 
 impl Room {
-    pub fn new(decks: Vec<Card>, database: Arc<Mutex<SqlitePool>>) -> Self {
+    pub fn new(
+        id: RoomId,
+        rules: TableRules,
+        access: Access,
+        database: Arc<Mutex<SqlitePool>>,
+        lobby: broadcast::Sender<LobbyEvent>,
+    ) -> Self {
+        let shoe_seed = fastrand::u64(..);
         Room {
+            id,
+            decks: Card::seeded_shoe(rules.decks, shoe_seed),
+            shoe_seed,
+            round: None,
+            rules,
+            access,
             started: false,
-            current_hand: 0,
+            finished: false,
+            host: None,
+            locked: false,
+            current: None,
+            next_hand_id: 0,
             dealer_hand: vec![],
             hands: vec![],
             sockets: Sockets(HashMap::new()),
-            decks,
+            players: HashMap::new(),
+            spectators: Sockets(HashMap::new()),
+            bots: HashMap::new(),
+            next_bot: 0,
+            chat: VecDeque::new(),
+            next_chat_id: 0,
+            training: None,
             db: database,
+            lobby,
         }
     }
 
     pub fn database(&self) -> Arc<Mutex<SqlitePool>> {
         self.db.clone()
     }
-    pub fn current_mut(&mut self) -> &mut Hand {
-        self.hands.get_mut(self.current_hand).unwrap()
+
+    pub fn phase(&self) -> Phase {
+        match (self.started, self.finished) {
+            (false, _) => Phase::Waiting,
+            (true, false) => Phase::Playing,
+            (true, true) => Phase::Finished,
+        }
     }
 
-    pub fn current(&self) -> &Hand {
-        self.hands.get(self.current_hand).unwrap()
+    /// Seats can only be taken or swapped while no round is being played
+    pub fn between_rounds(&self) -> bool {
+        self.phase() != Phase::Playing
     }
 
-    pub fn current_hand(&self) -> usize {
-        self.current_hand
+    pub fn is_joinable(&self) -> bool {
+        self.between_rounds() && !self.locked && self.free_seat(None).is_some()
     }
 
-    pub fn next_hand(&mut self) -> bool {
-        self.current_hand += 1;
-        self.current_hand == self.hands.len()
+    pub fn can_spectate(&self) -> bool {
+        self.rules
+            .max_spectators
+            .is_none_or(|max| self.spectators.len() < max)
+    }
+
+    /// Whether `user` has enough to make the smallest bet here. Anyone who
+    /// doesn't can only watch, or the table would wait on a bet they can't make
+    pub fn can_cover_min_bet(&self, user: &User) -> bool {
+        let balance = self
+            .training
+            .as_ref()
+            .map_or(user.balance, |training| training.balance);
+        balance >= i64::from(self.rules.min_bet)
+    }
+
+    /// What `account_id` has to bet with here, which is play money at a
+    /// training table
+    pub async fn balance(&self, account_id: i64) -> i64 {
+        if let Some(training) = &self.training {
+            return training.balance;
+        }
+        let database = self.db.lock().await;
+        sqlx::query_scalar!("SELECT balance FROM Users WHERE id = ?", account_id)
+            .fetch_one(&*database)
+            .await
+            .unwrap()
+    }
+
+    /// Changes a balance, or the play money at a training table, which never
+    /// reaches the ledger
+    pub async fn apply(&mut self, entry: Entry) {
+        match &mut self.training {
+            Some(training) => training.balance += entry.amount(),
+            None => {
+                entry.apply(&*self.db.lock().await).await;
+            }
+        }
+    }
+
+    /// Marks what the current hand's player chose at a training table, if
+    /// they really had a choice
+    pub async fn grade(&mut self, decision: Decision) {
+        let Some(training) = &mut self.training else {
+            return;
+        };
+        let current = self.current.unwrap();
+        let hand = self.hands.iter().find(|hand| hand.id == current).unwrap();
+        let total = hand.score().total();
+        if hand.hand.is_split_aces() || hand.hand.cards.len() == 10 || total >= 21 {
+            return;
+        }
+        let (correct, advice, reason) =
+            training.grade(&hand.hand, self.dealer_hand[1], &self.rules, decision);
+        let action = ServerAction::Graded {
+            correct,
+            advice,
+            reason,
+        };
+        self.notify_current(&action).await;
+    }
+
+    /// The Hi-Lo count of the shoe, from what everyone at the table has seen
+    pub fn count(&self) -> Count {
+        let hole_card = match self.phase() {
+            Phase::Playing => self.dealer_hand.first(),
+            _ => None,
+        };
+        Count::of(self.decks.iter().chain(hole_card))
     }
 
-    pub fn find_first_hand(&self, second: &Hand) -> usize {
-        if !second.is_second() {
-            return self.hands.iter().position(|p| p == second).unwrap();
+    pub fn is_listed(&self) -> bool {
+        self.access.visibility == Visibility::Public
+    }
+
+    /// Tells the lobby that something about this room has changed
+    pub fn publish(&self) {
+        if !self.is_listed() {
+            return;
         }
+        //Nobody may be watching the lobby, which is fine
+        let _ = self.lobby.send(LobbyEvent::Updated(RoomListing::new(self)));
+    }
+
+    pub fn is_host(&self, who: Who) -> bool {
+        self.host == Some(who)
+    }
+
+    /// Whether `who` can act on the hand being played right now
+    pub fn is_turn_of(&self, who: Who) -> bool {
+        self.phase() == Phase::Playing && self.current.is_some() && self.current().who == who
+    }
+
+    /// Sits a bot down at `seat`, playing with `user`'s account. Bots have no
+    /// connection, so they're told apart by an address nobody can connect from
+    pub fn add_bot(&mut self, kind: BotKind, user: User, seat: usize) -> (Who, HandId) {
+        self.next_bot += 1;
+        let who = SocketAddr::from((Ipv4Addr::UNSPECIFIED, self.next_bot));
+        let hand = self.take_seat(who, seat, user.id);
+        self.players.insert(who, user);
+        self.bots.insert(who, kind);
+        (who, hand)
+    }
+
+    /// Every seat with someone in it, in the order they're played
+    pub fn taken_seats(&self) -> Vec<usize> {
         self.hands
             .iter()
-            .enumerate()
-            .find(|(_, hand)| !hand.second_hand && hand.who == second.who)
+            .filter(|hand| !hand.is_second())
+            .map(|hand| hand.seat)
+            .collect()
+    }
+
+    pub fn seats_of(&self, who: Who) -> Vec<usize> {
+        self.hands
+            .iter()
+            .filter(|hand| hand.who == who && !hand.is_second())
+            .map(|hand| hand.seat)
+            .collect()
+    }
+
+    pub fn seated_at(&self, seat: usize) -> Option<Who> {
+        self.hands
+            .iter()
+            .find(|hand| hand.seat == seat && !hand.is_second())
+            .map(|hand| hand.who)
+    }
+
+    /// `wanted` if it's free, otherwise the lowest numbered free seat
+    pub fn free_seat(&self, wanted: Option<usize>) -> Option<usize> {
+        let free = |seat: &usize| *seat < self.rules.max_players && self.seated_at(*seat).is_none();
+        wanted
+            .filter(free)
+            .or_else(|| (0..self.rules.max_players).find(free))
+    }
+
+    pub fn new_hand_id(&mut self) -> HandId {
+        self.next_hand_id += 1;
+        HandId(self.next_hand_id)
+    }
+
+    /// Sits `who` down at `seat`, keeping the hands in seat order. Returns
+    /// the id of their hand there
+    pub fn take_seat(&mut self, who: Who, seat: usize, account_id: i64) -> HandId {
+        let id = self.new_hand_id();
+        let idx = self.hands.partition_point(|hand| hand.seat < seat);
+        let hand = Hand::new(id, who, seat, engine::Hand::default(), account_id);
+        self.hands.insert(idx, hand);
+        id
+    }
+
+    /// Moves whoever sits at `from` to `to`, hands and all
+    pub fn move_seat(&mut self, from: usize, to: usize) {
+        for hand in self.hands.iter_mut().filter(|hand| hand.seat == from) {
+            hand.seat = to;
+        }
+        self.hands.sort_by_key(|hand| (hand.seat, hand.is_second()));
+    }
+
+    pub fn leave_seat(&mut self, seat: usize) {
+        self.hands.retain(|hand| hand.seat != seat);
+    }
+
+    /// The id of the first hand played from `seat`
+    pub fn hand_at(&self, seat: usize) -> HandId {
+        self.hands
+            .iter()
+            .find(|hand| hand.seat == seat && !hand.is_second())
+            .unwrap()
+            .id
+    }
+
+    pub fn hand(&self, id: HandId) -> Option<&Hand> {
+        self.hands.iter().find(|hand| hand.id == id)
+    }
+
+    /// Takes every hand `who` is playing off the table, keeping the turn with
+    /// whichever hand had it. Returns the seats they sat in and whether it was
+    /// their turn
+    pub fn remove_hands(&mut self, who: Who) -> (Vec<usize>, bool) {
+        let seats = self.seats_of(who);
+        let was_current = self.current.is_some() && self.current().who == who;
+        if was_current {
+            //The turn passes to the next hand that isn't theirs
+            self.current = self
+                .hands
+                .iter()
+                .skip_while(|hand| Some(hand.id) != self.current)
+                .find(|hand| hand.who != who)
+                .map(|hand| hand.id);
+        }
+        self.hands.retain(|hand| hand.who != who);
+        (seats, was_current)
+    }
+
+    /// Clears the table for another round, reshuffling once the shoe runs low
+    pub fn reset_round(&mut self) {
+        self.hands.retain(|hand| !hand.is_second());
+        for hand in &mut self.hands {
+            hand.hand.clear();
+            hand.bet = 0;
+        }
+        self.dealer_hand.clear();
+        self.current = None;
+        self.started = false;
+        self.finished = false;
+        if self.rules.needs_shuffle(self.decks.len()) {
+            self.shoe_seed = fastrand::u64(..);
+            self.decks = Card::seeded_shoe(self.rules.decks, self.shoe_seed);
+        }
+    }
+
+    /// Starts keeping track of the round about to be dealt
+    pub fn start_log(&mut self) {
+        let dealt = usize::from(self.rules.decks) * 52 - self.decks.len();
+        let table = self.replay_state();
+        self.round = Some(RoundLog::new(self.shoe_seed, dealt, &table));
+    }
+
+    /// Notes down what the current hand's player just did
+    pub fn log(&mut self, action: Move, amount: Option<u32>, card: Option<Card>) {
+        let current = self.current();
+        let (hand, user_id) = (current.id, current.account_id);
+        if let Some(round) = &mut self.round {
+            round.record(hand, user_id, action, amount, card);
+        }
+    }
+
+    pub fn current_mut(&mut self) -> &mut Hand {
+        let current = self.current.unwrap();
+        self.hands
+            .iter_mut()
+            .find(|hand| hand.id == current)
             .unwrap()
-            .0
+    }
+
+    pub fn current(&self) -> &Hand {
+        self.hand(self.current.unwrap()).unwrap()
+    }
+
+    pub fn current_id(&self) -> Option<HandId> {
+        self.current
+    }
+
+    /// Gives the first hand at the table its turn
+    pub fn first_turn(&mut self) {
+        self.current = self.hands.first().map(|hand| hand.id);
+    }
+
+    /// Passes the turn on, returning whether every hand has now had one
+    pub fn next_hand(&mut self) -> bool {
+        self.current = self
+            .hands
+            .iter()
+            .skip_while(|hand| Some(hand.id) != self.current)
+            .nth(1)
+            .map(|hand| hand.id);
+        self.current.is_none()
     }
 
     pub async fn notify_current(&mut self, action: &ServerAction) {
         let who = *self.current().who();
-        let socket = self.sockets.get_mut(&who).unwrap();
+        self.notify_player(who, action).await;
+    }
+
+    /// Sends `action` to just `who`, if they're still connected
+    pub async fn notify_player(&mut self, who: Who, action: &ServerAction) {
+        let Some(socket) = self.sockets.get_mut(&who) else {
+            return;
+        };
         let msg = serde_json::to_string(action).unwrap();
-        socket.send(Message::Text(msg)).await.unwrap();
+        let _ = socket.send(Message::Text(msg)).await;
     }
 
     pub async fn notify_all(&mut self, action: &ServerAction) {
-        self.sockets.notify(action).await
+        self.sockets.notify(action).await;
+        self.spectators.broadcast(action).await;
+        let table_talk = matches!(
+            action,
+            ServerAction::Chat { .. } | ServerAction::Spectators { .. }
+        );
+        if let Some(round) = self.round.as_mut().filter(|_| !table_talk) {
+            round.replay(action);
+        }
     }
 
-    pub fn dealer_hand_dummy(&self) -> Hand {
-        Hand {
-            second_hand: false,
-            //TODO: This is hacky as hell
-            who: self.hands.first().unwrap().who,
-            hand: self.dealer_hand.clone(),
-            account_id: 0,
-            bet: 0,
+    /// Adds a message from `user` to the room's history
+    pub fn post_chat(&mut self, user: &User, text: String) -> ChatMessage {
+        let message = ChatMessage {
+            id: self.next_chat_id,
+            user_id: user.id,
+            from: PlayerInfo::from(user),
+            text,
+            at: unix_time(),
+        };
+        self.next_chat_id += 1;
+        if self.chat.len() == chat::HISTORY {
+            self.chat.pop_front();
+        }
+        self.chat.push_back(message.clone());
+        message
+    }
+
+    /// Everything a newcomer needs to draw the table as it is right now. The
+    /// dealer's hole card stays hidden until the round is over
+    pub fn table_state(&self) -> ServerAction {
+        let phase = self.phase();
+        let hands = self
+            .hands
+            .iter()
+            .map(|hand| SeenHand {
+                id: hand.id,
+                seat: hand.seat,
+                second_hand: hand.is_second(),
+                cards: hand.hand.cards.clone(),
+                bet: hand.bet,
+                player: PlayerInfo::from(&self.players[&hand.who]),
+            })
+            .collect();
+        let dealer = self
+            .dealer_hand
+            .iter()
+            .enumerate()
+            .map(|(idx, card)| (idx != 0 || phase != Phase::Playing).then_some(*card))
+            .collect();
+        let current = match phase {
+            Phase::Playing => self.current,
+            _ => None,
+        };
+        ServerAction::TableState {
+            phase,
+            hands,
+            dealer,
+            current,
+            host: self
+                .host
+                .and_then(|host| self.seats_of(host).first().copied()),
+            locked: self.locked,
+            spectators: self.spectators.len(),
+            chat: self.chat.iter().cloned().collect(),
         }
     }
+
+    /// The table as a replay shows it, without the chat around it or anyone
+    /// who was watching
+    pub fn replay_state(&self) -> ServerAction {
+        let mut table = self.table_state();
+        if let ServerAction::TableState {
+            chat, spectators, ..
+        } = &mut table
+        {
+            chat.clear();
+            *spectators = 0;
+        }
+        table
+    }
+}
+
+/// A hand as anyone at the table can see it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SeenHand {
+    pub id: HandId,
+    pub seat: usize,
+    pub second_hand: bool,
+    pub cards: Vec<Card>,
+    pub bet: u32,
+    pub player: PlayerInfo,
 }
 
 pub struct MyState {
     pub rooms: HashMap<RoomId, Room>,
+    pub lobby: broadcast::Sender<LobbyEvent>,
+    /// How quickly each user can send chat messages, across every room
+    pub chat_limits: Buckets<i64>,
 }
 
 impl MyState {
     pub fn new() -> Self {
+        let (lobby, _) = broadcast::channel(64);
         Self {
             rooms: HashMap::new(),
+            lobby,
+            chat_limits: Buckets::new(5, Duration::from_secs(2)),
         }
     }
+
+    pub fn remove_room(&mut self, id: &RoomId) -> Option<Room> {
+        let room = self.rooms.remove(id)?;
+        let _ = self.lobby.send(LobbyEvent::Removed { id: id.to_string() });
+        Some(room)
+    }
+
+    /// Whether `account_id` has real money on a hand that hasn't been settled
+    pub fn has_live_bet(&self, account_id: i64) -> bool {
+        self.rooms
+            .values()
+            .filter(|room| room.training.is_none())
+            .flat_map(|room| &room.hands)
+            .any(|hand| hand.account_id == account_id && hand.bet > 0)
+    }
+
+    pub fn listings(&self) -> Vec<RoomListing> {
+        let mut listings = self
+            .rooms
+            .values()
+            .filter(|room| room.is_listed())
+            .map(RoomListing::new)
+            .collect::<Vec<_>>();
+        listings.sort_by(|a, b| a.id.cmp(&b.id));
+        listings
+    }
 }
 
 #[nutype(
@@ -154,72 +625,66 @@ pub fn new_id() -> RoomId {
     RoomId::new(id).unwrap()
 }
 
+/// Names a hand for as long as it's on the table. Unlike its position in
+/// `Room::hands`, it doesn't change when other hands come and go
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct HandId(u32);
+
+impl HandId {
+    pub fn get(self) -> u32 {
+        self.0
+    }
+}
+
 #[derive(PartialEq, Eq)]
 pub struct Hand {
-    second_hand: bool,
+    id: HandId,
     who: SocketAddr,
-    pub hand: Vec<Card>,
+    seat: usize,
+    pub hand: engine::Hand,
     account_id: i64,
     pub bet: u32,
 }
 
 impl Hand {
-    pub fn new(who: SocketAddr, hand: Vec<Card>, second_hand: bool, account_id: i64) -> Hand {
+    pub fn new(
+        id: HandId,
+        who: SocketAddr,
+        seat: usize,
+        hand: engine::Hand,
+        account_id: i64,
+    ) -> Hand {
         Hand {
+            id,
             who,
+            seat,
             hand,
-            second_hand,
             account_id,
             bet: 0,
         }
     }
-    pub fn score(&self) -> Score {
-        let mut score = 0;
-        let mut found_ace = false;
-        for card in self.hand.iter() {
-            if card.rank == Rank::Ace {
-                found_ace = true;
-            }
-            score += card.score_card();
-        }
 
-        if found_ace && score < 12 {
-            score += 10;
-        }
-
-        match score.cmp(&21) {
-            std::cmp::Ordering::Equal if self.hand.len() == 2 => Score::Blackjack,
-            std::cmp::Ordering::Equal => Score::Points(score),
-            std::cmp::Ordering::Less => Score::Points(score),
-            std::cmp::Ordering::Greater => Score::Bust(score),
-        }
+    pub fn score(&self) -> Score {
+        self.hand.score()
     }
+
     pub fn who(&self) -> &SocketAddr {
         &self.who
     }
 
-    pub fn is_second(&self) -> bool {
-        self.second_hand
+    pub fn id(&self) -> HandId {
+        self.id
     }
 
-    pub fn account_id(&self) -> i64 {
-        self.account_id
+    pub fn seat(&self) -> usize {
+        self.seat
     }
-}
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-pub enum Score {
-    Bust(u8),
-    Points(u8),
-    Blackjack,
-}
-
-impl Score {
-    pub fn is_blackjack(&self) -> bool {
-        *self == Score::Blackjack
+    pub fn is_second(&self) -> bool {
+        self.hand.is_second()
     }
 
-    pub fn is_bust(&self) -> bool {
-        matches!(self, Score::Bust(_))
+    pub fn account_id(&self) -> i64 {
+        self.account_id
     }
 }
@@ -0,0 +1,224 @@
+//! Where money comes from away from the tables: a bonus once a day, and a way
+//! back in for anyone who can't afford a bet any more
+
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    response::{IntoResponse, Redirect},
+    Extension, Form,
+};
+use axum_login::axum_sessions::extractors::ReadableSession;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use sqlx::SqlitePool;
+
+use crate::{
+    auth, csrf,
+    data::unix_time,
+    ledger::{Entry, LedgerKind},
+    routes::{error_400, error_403, error_429, Stakes},
+    AppState, User,
+};
+
+const DAY: i64 = 24 * 60 * 60;
+const HOUR: i64 = 60 * 60;
+
+/// The amounts involved, set from the environment when the server starts
+pub struct Economy {
+    /// Given on the first login of each day (UTC), or nothing if 0
+    pub daily_bonus: i64,
+    /// What a rebuy brings a balance back up to
+    pub rebuy_to: i64,
+    /// How long someone has to wait between rebuys
+    pub rebuy_every: i64,
+}
+
+impl Economy {
+    fn from_env() -> Self {
+        fn var(name: &str, default: i64) -> i64 {
+            match std::env::var(name) {
+                Ok(value) => value
+                    .parse()
+                    .unwrap_or_else(|_| panic!("{name} should be a whole number, not {value}")),
+                Err(_) => default,
+            }
+        }
+        Self {
+            daily_bonus: var("DAILY_BONUS", 250),
+            rebuy_to: var("REBUY_AMOUNT", 1000),
+            rebuy_every: var("REBUY_HOURS", 12) * HOUR,
+        }
+    }
+}
+
+pub static ECONOMY: Lazy<Economy> = Lazy::new(Economy::from_env);
+
+/// Reads the amounts now, so a bad setting stops the server from starting
+pub fn economy_force() {
+    let economy = Lazy::force(&ECONOMY);
+    if economy.daily_bonus < 0 {
+        eprintln!("DAILY_BONUS can't be negative");
+        std::process::exit(1)
+    }
+    if economy.rebuy_every < 0 {
+        eprintln!("REBUY_HOURS can't be negative");
+        std::process::exit(1)
+    }
+    if economy.rebuy_to < lowest_min_bet() {
+        eprintln!("REBUY_AMOUNT has to be enough for at least one bet");
+        std::process::exit(1)
+    }
+    println!(
+        "Daily bonus is {}, rebuys go up to {} every {}h",
+        economy.daily_bonus,
+        economy.rebuy_to,
+        economy.rebuy_every / HOUR
+    );
+}
+
+/// Below this, there's no table left to bet at. Rebuys go by this rather
+/// than the stakes of any one table
+pub fn lowest_min_bet() -> i64 {
+    i64::from(Stakes::Low.bets().0)
+}
+
+/// Gives `user_id` today's bonus unless they already have it, returning how
+/// much was given
+pub async fn claim_daily_bonus(conn: &SqlitePool, user_id: i64) -> Option<i64> {
+    let bonus = ECONOMY.daily_bonus;
+    if bonus <= 0 {
+        return None;
+    }
+    let today = unix_time() / DAY * DAY;
+    let claimed = sqlx::query_scalar!(
+        r#"SELECT EXISTS (
+            SELECT 1 FROM Ledger WHERE user_id = ? AND kind = 'bonus' AND created_at >= ?
+        ) AS "claimed!: bool""#,
+        user_id,
+        today
+    )
+    .fetch_one(conn)
+    .await
+    .unwrap();
+    if claimed {
+        return None;
+    }
+    Entry::new(user_id, bonus, LedgerKind::Bonus)
+        .note("Daily bonus")
+        .apply(conn)
+        .await;
+    Some(bonus)
+}
+
+/// Why someone can't rebuy right now
+pub enum RebuyRefused {
+    /// They can still afford a bet
+    NotBroke,
+    /// What they have on the table hasn't been settled yet, and might win
+    BetInPlay,
+    /// They rebought too recently, and have this many seconds left to wait
+    TooSoon(i64),
+}
+
+/// Whether `user_id`, with `balance`, could rebuy now. Bets are taken from
+/// the balance when they're placed, so someone `betting` isn't broke yet
+pub async fn can_rebuy(
+    conn: &SqlitePool,
+    user_id: i64,
+    balance: i64,
+    betting: bool,
+) -> Result<(), RebuyRefused> {
+    if balance >= lowest_min_bet() {
+        return Err(RebuyRefused::NotBroke);
+    }
+    if betting {
+        return Err(RebuyRefused::BetInPlay);
+    }
+    let last = sqlx::query_scalar!(
+        r#"SELECT MAX(created_at) AS "last: i64" FROM Ledger WHERE user_id = ? AND kind = 'rebuy'"#,
+        user_id
+    )
+    .fetch_one(conn)
+    .await
+    .unwrap();
+    match last {
+        Some(last) if last + ECONOMY.rebuy_every > unix_time() => Err(RebuyRefused::TooSoon(
+            last + ECONOMY.rebuy_every - unix_time(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Bots play with accounts of their own, so are topped back up to a starting
+/// balance whenever they sit down
+pub async fn top_up_bot(conn: &SqlitePool, bot: &User) {
+    if bot.balance < auth::STARTING_BALANCE {
+        Entry::new(
+            bot.id,
+            auth::STARTING_BALANCE - bot.balance,
+            LedgerKind::Rebuy,
+        )
+        .note("Bot top-up")
+        .apply(conn)
+        .await;
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CsrfForm {
+    csrf_token: String,
+}
+
+pub async fn rebuy(
+    ConnectInfo(who): ConnectInfo<SocketAddr>,
+    State((state, database)): State<AppState>,
+    Extension(user): Extension<User>,
+    session: ReadableSession,
+    Form(form): Form<CsrfForm>,
+) -> impl IntoResponse {
+    if !csrf::verify(&session, &form.csrf_token) {
+        println!(
+            "{} ({who}) tried to rebuy without a valid CSRF token",
+            user.username
+        );
+        return error_403().into_response();
+    }
+    let betting = state.lock().await.has_live_bet(user.id);
+    let conn = database.lock().await;
+    let balance = sqlx::query_scalar!("SELECT balance FROM Users WHERE id = ?", user.id)
+        .fetch_one(&*conn)
+        .await
+        .unwrap();
+    match can_rebuy(&conn, user.id, balance, betting).await {
+        Ok(()) => {
+            let balance = Entry::new(user.id, ECONOMY.rebuy_to - balance, LedgerKind::Rebuy)
+                .note("Rebuy")
+                .apply(&conn)
+                .await;
+            println!("{} ({who}) rebought, and now has {balance}", user.username);
+            Redirect::to("/").into_response()
+        }
+        Err(RebuyRefused::NotBroke) => {
+            println!(
+                "{} ({who}) tried to rebuy with {balance} left",
+                user.username
+            );
+            error_400().into_response()
+        }
+        Err(RebuyRefused::BetInPlay) => {
+            println!(
+                "{} ({who}) tried to rebuy with a bet still in play",
+                user.username
+            );
+            error_400().into_response()
+        }
+        Err(RebuyRefused::TooSoon(remaining)) => {
+            println!(
+                "{} ({who}) tried to rebuy again with {remaining}s to wait",
+                user.username
+            );
+            error_429().into_response()
+        }
+    }
+}
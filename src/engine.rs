@@ -0,0 +1,356 @@
+//! The rules of the game, free of any connections or storage, so they can be
+//! played by the server and by offline tools alike
+
+use serde::{Deserialize, Serialize};
+
+use crate::card::{Card, Rank};
+
+pub const MAX_PLAYERS: usize = 6;
+
+/// The house rules a room is played under, fixed when it is created
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableRules {
+    pub decks: u8,
+    pub dealer_hits_soft_17: bool,
+    pub min_bet: u32,
+    pub max_bet: u32,
+    /// How many seats the table has
+    pub max_players: usize,
+    /// How many of those seats one account may play at once
+    pub max_seats_per_player: usize,
+    /// How many people may watch without a seat, if there's a limit at all
+    pub max_spectators: Option<usize>,
+    /// Whether basic strategy may count on doubling a split hand
+    pub double_after_split: bool,
+    /// Whether players can ask for basic strategy hints
+    pub hints: bool,
+}
+
+impl Default for TableRules {
+    fn default() -> Self {
+        Self {
+            decks: 8,
+            dealer_hits_soft_17: false,
+            min_bet: 10,
+            max_bet: 500,
+            max_players: MAX_PLAYERS,
+            max_seats_per_player: 1,
+            max_spectators: None,
+            double_after_split: true,
+            hints: true,
+        }
+    }
+}
+
+impl TableRules {
+    pub fn summary(&self) -> String {
+        let decks = match self.decks {
+            1 => "Single deck".to_string(),
+            n => format!("{n} decks"),
+        };
+        let soft_17 = if self.dealer_hits_soft_17 {
+            "dealer hits soft 17"
+        } else {
+            "dealer stands on soft 17"
+        };
+        format!("{decks}, {soft_17}, blackjack pays 3:2")
+    }
+
+    /// Whether a shoe with `remaining` cards left should be replaced before
+    /// the next round
+    pub fn needs_shuffle(&self, remaining: usize) -> bool {
+        let cut_card = (usize::from(self.decks) * 52 / 4).max(52);
+        remaining < cut_card
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Score {
+    Bust(u8),
+    Points(u8),
+    Blackjack,
+}
+
+impl Score {
+    pub fn of(cards: &[Card]) -> Score {
+        let mut score = 0;
+        let mut found_ace = false;
+        for card in cards {
+            if card.rank == Rank::Ace {
+                found_ace = true;
+            }
+            score += card.score_card();
+        }
+
+        if found_ace && score < 12 {
+            score += 10;
+        }
+
+        match score.cmp(&21) {
+            std::cmp::Ordering::Equal if cards.len() == 2 => Score::Blackjack,
+            std::cmp::Ordering::Equal => Score::Points(score),
+            std::cmp::Ordering::Less => Score::Points(score),
+            std::cmp::Ordering::Greater => Score::Bust(score),
+        }
+    }
+
+    /// The total, with blackjack counted as 21
+    pub fn total(self) -> u8 {
+        match self {
+            Score::Points(points) | Score::Bust(points) => points,
+            Score::Blackjack => 21,
+        }
+    }
+
+    pub fn is_blackjack(&self) -> bool {
+        *self == Score::Blackjack
+    }
+
+    pub fn is_bust(&self) -> bool {
+        matches!(self, Score::Bust(_))
+    }
+}
+
+/// Whether an ace is being counted as 11
+pub fn is_soft(cards: &[Card]) -> bool {
+    let hard: u8 = cards.iter().map(Card::score_card).sum();
+    cards.iter().any(|card| card.rank == Rank::Ace) && hard + 10 <= 21
+}
+
+/// A player's cards, and whether they came from a split
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Hand {
+    pub cards: Vec<Card>,
+    second_hand: bool,
+    /// Whether this first hand has had a second split off it this round
+    split: bool,
+}
+
+impl Hand {
+    pub fn new(cards: Vec<Card>, second_hand: bool) -> Self {
+        Self {
+            cards,
+            second_hand,
+            split: false,
+        }
+    }
+
+    /// A split hand making 21 with two cards is just 21, not a blackjack
+    pub fn score(&self) -> Score {
+        match Score::of(&self.cards) {
+            Score::Blackjack if self.is_split() => Score::Points(21),
+            score => score,
+        }
+    }
+
+    pub fn is_soft(&self) -> bool {
+        is_soft(&self.cards)
+    }
+
+    /// Only a first hand of two cards worth the same can be split
+    pub fn can_split(&self) -> bool {
+        !self.second_hand
+            && self.cards.len() == 2
+            && self.cards[0].score_card() == self.cards[1].score_card()
+    }
+
+    pub fn is_second(&self) -> bool {
+        self.second_hand
+    }
+
+    /// Whether this hand is one of two made by splitting
+    pub fn is_split(&self) -> bool {
+        self.second_hand || self.split
+    }
+
+    /// Takes the second card off to start a new hand with, returning it
+    pub fn split(&mut self) -> Hand {
+        self.split = true;
+        Hand::new(vec![self.cards.pop().unwrap()], true)
+    }
+
+    /// Gets the hand ready for the next round
+    pub fn clear(&mut self) {
+        self.cards.clear();
+        self.split = false;
+    }
+
+    /// Split aces only get one more card each
+    pub fn is_split_aces(&self) -> bool {
+        self.is_split() && self.cards[0].rank == Rank::Ace
+    }
+}
+
+/// Whether the dealer has to take another card
+pub fn dealer_hits(cards: &[Card], rules: &TableRules) -> bool {
+    let hits_soft_17 = rules.dealer_hits_soft_17 && is_soft(cards);
+    match Score::of(cards) {
+        Score::Bust(_) | Score::Blackjack => false,
+        Score::Points(17) => hits_soft_17,
+        Score::Points(x) => x < 17,
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameResult {
+    Lose,
+    Win,
+    Push,
+    Blackjack,
+}
+
+impl GameResult {
+    /// How much is paid back on a bet of `bet`, including the stake
+    pub fn payout(self, bet: u32) -> i64 {
+        let amount = i64::from(bet);
+        match self {
+            GameResult::Lose => 0,
+            GameResult::Win => amount * 2,
+            GameResult::Push => amount,
+            GameResult::Blackjack => (2 * amount) + (amount / 2),
+        }
+    }
+}
+
+/// Settles every hand at the table against the dealer's
+pub fn results(hands: &[Score], dealer: Score) -> Vec<GameResult> {
+    if let Score::Bust(p) = dealer {
+        if hands.iter().all(Score::is_bust) {
+            return hands
+                .iter()
+                .map(|score| match score {
+                    Score::Bust(c) if *c == p => GameResult::Push,
+                    Score::Bust(c) if *c > p => GameResult::Win,
+                    Score::Bust(c) if *c < p => GameResult::Lose,
+                    _ => unreachable!(),
+                })
+                .collect();
+        }
+    };
+    //TODO: A split hand making 21 with two cards should still win against an
+    // ordinary 21 made of more than two cards, but for now it pushes
+    hands
+        .iter()
+        .map(|&score| {
+            if score.is_bust() {
+                return GameResult::Lose;
+            }
+            match dealer {
+                Score::Blackjack => {
+                    if score.is_blackjack() {
+                        GameResult::Push
+                    } else {
+                        GameResult::Lose
+                    }
+                }
+                Score::Bust(_) => {
+                    if score.is_blackjack() {
+                        GameResult::Blackjack
+                    } else {
+                        GameResult::Win
+                    }
+                }
+                Score::Points(points) => match score {
+                    Score::Blackjack => GameResult::Blackjack,
+                    Score::Points(p) if p > points => GameResult::Win,
+                    Score::Points(p) if p < points => GameResult::Lose,
+                    Score::Bust(_) => unreachable!(),
+                    _ => GameResult::Push,
+                },
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::Suit;
+
+    fn cards(ranks: &[Rank]) -> Vec<Card> {
+        ranks
+            .iter()
+            .map(|&rank| Card {
+                suit: Suit::Hearts,
+                rank,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn blackjack_pays_3_to_2() {
+        let blackjack = Score::of(&cards(&[Rank::Ace, Rank::King]));
+        assert_eq!(blackjack, Score::Blackjack);
+        let results = results(&[blackjack], Score::Points(20));
+        assert_eq!(results, [GameResult::Blackjack]);
+        assert_eq!(GameResult::Blackjack.payout(10), 25);
+        assert_eq!(GameResult::Win.payout(10), 20);
+        assert_eq!(GameResult::Push.payout(10), 10);
+        assert_eq!(GameResult::Lose.payout(10), 0);
+    }
+
+    #[test]
+    fn a_bust_loses_even_if_the_dealer_busts() {
+        let results = results(&[Score::Bust(22), Score::Points(12)], Score::Bust(25));
+        assert_eq!(results, [GameResult::Lose, GameResult::Win]);
+    }
+
+    #[test]
+    fn when_everyone_busts_the_totals_are_compared() {
+        let results = results(
+            &[Score::Bust(22), Score::Bust(24), Score::Bust(26)],
+            Score::Bust(24),
+        );
+        assert_eq!(
+            results,
+            [GameResult::Lose, GameResult::Push, GameResult::Win]
+        );
+    }
+
+    #[test]
+    fn split_aces_are_not_blackjack() {
+        let mut aces = Hand::new(cards(&[Rank::Ace, Rank::Ace]), false);
+        let mut second = aces.split();
+        aces.cards.extend(cards(&[Rank::King]));
+        second.cards.extend(cards(&[Rank::Queen]));
+        assert!(aces.is_split_aces() && second.is_split_aces());
+        assert_eq!(aces.score(), Score::Points(21));
+        assert_eq!(second.score(), Score::Points(21));
+        let results = results(&[aces.score(), second.score()], Score::Points(20));
+        assert_eq!(results, [GameResult::Win, GameResult::Win]);
+    }
+
+    #[test]
+    fn soft_17_follows_the_table() {
+        let soft_17 = cards(&[Rank::Ace, Rank::Six]);
+        let h17 = TableRules {
+            dealer_hits_soft_17: true,
+            ..TableRules::default()
+        };
+        assert!(dealer_hits(&soft_17, &h17));
+        assert!(!dealer_hits(&soft_17, &TableRules::default()));
+        let hard_17 = cards(&[Rank::Ten, Rank::Seven]);
+        assert!(!dealer_hits(&hard_17, &h17));
+        assert!(dealer_hits(
+            &cards(&[Rank::Ten, Rank::Six]),
+            &TableRules::default()
+        ));
+    }
+
+    #[test]
+    fn shuffles_at_the_cut_card() {
+        let rules = TableRules {
+            decks: 8,
+            ..TableRules::default()
+        };
+        assert!(!rules.needs_shuffle(104));
+        assert!(rules.needs_shuffle(103));
+        //Never less than a deck, however few decks there are
+        let single = TableRules {
+            decks: 1,
+            ..TableRules::default()
+        };
+        assert!(single.needs_shuffle(51));
+        assert!(!single.needs_shuffle(52));
+    }
+}
@@ -0,0 +1,400 @@
+//! A record of every round played, kept to settle disputes and work out
+//! statistics from
+
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        ConnectInfo, Path, Query, State,
+    },
+    http::HeaderMap,
+    response::{Html, IntoResponse},
+    routing::get,
+    Extension, Router,
+};
+use axum_login::RequireAuthorizationLayer;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::{
+    card::Card,
+    chat, csrf,
+    data::{unix_time, HandId, RoomId},
+    engine::{GameResult, TableRules},
+    limit::RateLimitLayer,
+    routes::{error_400, error_403, error_404, TERA},
+    AppState, Role, ServerAction, User,
+};
+
+/// The fastest a replay can be played back
+const MAX_SPEED: u32 = 16;
+
+/// Routes under `/history`, for watching rounds again
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(recent_rounds))
+        .route("/:round", get(replay_page))
+        .route(
+            "/:round/ws",
+            get(replay_ws).layer(RateLimitLayer::new(10, Duration::from_secs(6))),
+        )
+        .route_layer(RequireAuthorizationLayer::<i64, User, Role>::login())
+}
+
+/// What a player did with a hand
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum Move {
+    Bet,
+    Hit,
+    Stand,
+    Split,
+}
+
+struct Logged {
+    hand: HandId,
+    user_id: i64,
+    action: Move,
+    /// What was bet, for bets and splits
+    amount: Option<u32>,
+    /// The card a hit was dealt
+    card: Option<Card>,
+    at: i64,
+}
+
+/// How a hand at the table ended up
+pub struct HandRecord {
+    pub hand: HandId,
+    pub seat: usize,
+    pub user_id: i64,
+    pub second_hand: bool,
+    pub cards: Vec<Card>,
+    pub bet: u32,
+    pub result: GameResult,
+    /// What was paid back, including the stake
+    pub payout: i64,
+}
+
+/// The round being played, kept until it's over and can be saved
+pub struct RoundLog {
+    started_at: i64,
+    started: Instant,
+    shoe_seed: u64,
+    /// How many cards had been dealt from the shoe before the round
+    shoe_position: usize,
+    actions: Vec<Logged>,
+    /// Everything sent to the whole table, and how long into the round
+    events: Vec<(i64, String)>,
+}
+
+impl RoundLog {
+    /// Starts a round from how the table looks before it's dealt
+    pub fn new(shoe_seed: u64, shoe_position: usize, table: &ServerAction) -> Self {
+        let mut log = Self {
+            started_at: unix_time(),
+            started: Instant::now(),
+            shoe_seed,
+            shoe_position,
+            actions: vec![],
+            events: vec![],
+        };
+        log.replay(table);
+        log
+    }
+
+    /// Keeps `action` to be sent again when the round is replayed
+    pub fn replay(&mut self, action: &ServerAction) {
+        let after = self.started.elapsed().as_millis() as i64;
+        self.events
+            .push((after, serde_json::to_string(action).unwrap()));
+    }
+
+    pub fn record(
+        &mut self,
+        hand: HandId,
+        user_id: i64,
+        action: Move,
+        amount: Option<u32>,
+        card: Option<Card>,
+    ) {
+        self.actions.push(Logged {
+            hand,
+            user_id,
+            action,
+            amount,
+            card,
+            at: unix_time(),
+        });
+    }
+
+    /// Writes the round out, returning its id
+    pub async fn save(
+        self,
+        conn: &SqlitePool,
+        room: &RoomId,
+        rules: &TableRules,
+        training: bool,
+        dealer: &[Card],
+        hands: Vec<HandRecord>,
+    ) -> i64 {
+        let now = unix_time();
+        let room = room.to_string();
+        let rules = serde_json::to_string(rules).unwrap();
+        //SQLite only has signed integers, so the seed is stored as its bits
+        let seed = self.shoe_seed as i64;
+        let position = self.shoe_position as i64;
+        let dealer = serde_json::to_string(dealer).unwrap();
+        let mut transaction = conn.begin().await.unwrap();
+        let round = sqlx::query_scalar!(
+            r#"INSERT INTO Rounds (room, rules, shoe_seed, shoe_position, dealer_cards, training,
+                started_at, finished_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING id AS "id!""#,
+            room,
+            rules,
+            seed,
+            position,
+            dealer,
+            training,
+            self.started_at,
+            now
+        )
+        .fetch_one(&mut transaction)
+        .await
+        .unwrap();
+        for hand in hands {
+            let id = hand.hand.get();
+            let seat = hand.seat as i64;
+            let cards = serde_json::to_string(&hand.cards).unwrap();
+            let result = format!("{:?}", hand.result);
+            sqlx::query!(
+                "INSERT INTO RoundHands (round_id, hand, seat, user_id, second_hand, cards, bet,
+                    result, payout)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                round,
+                id,
+                seat,
+                hand.user_id,
+                hand.second_hand,
+                cards,
+                hand.bet,
+                result,
+                hand.payout
+            )
+            .execute(&mut transaction)
+            .await
+            .unwrap();
+        }
+        for (seq, action) in self.actions.into_iter().enumerate() {
+            let seq = seq as i64;
+            let hand = action.hand.get();
+            let card = action
+                .card
+                .map(|card| serde_json::to_string(&card).unwrap());
+            sqlx::query!(
+                "INSERT INTO RoundActions (round_id, seq, hand, user_id, action, amount, card, at)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+                round,
+                seq,
+                hand,
+                action.user_id,
+                action.action,
+                action.amount,
+                card,
+                action.at
+            )
+            .execute(&mut transaction)
+            .await
+            .unwrap();
+        }
+        for (seq, (after, action)) in self.events.into_iter().enumerate() {
+            let seq = seq as i64;
+            sqlx::query!(
+                "INSERT INTO RoundEvents (round_id, seq, after_ms, action) VALUES (?, ?, ?, ?)",
+                round,
+                seq,
+                after,
+                action
+            )
+            .execute(&mut transaction)
+            .await
+            .unwrap();
+        }
+        transaction.commit().await.unwrap();
+        round
+    }
+}
+
+/// Players may watch rounds they played in, and admins any round at all
+async fn can_watch(conn: &SqlitePool, round: i64, user: &User) -> bool {
+    if user.role == Role::Admin {
+        return true;
+    }
+    sqlx::query_scalar!(
+        r#"SELECT EXISTS (
+            SELECT 1 FROM RoundHands WHERE round_id = ? AND user_id = ?
+            UNION ALL
+            SELECT 1 FROM RoundActions WHERE round_id = ? AND user_id = ?
+        ) AS "played!: bool""#,
+        round,
+        user.id,
+        round,
+        user.id
+    )
+    .fetch_one(conn)
+    .await
+    .unwrap()
+}
+
+#[derive(Serialize)]
+struct RoundSummary {
+    id: i64,
+    room: String,
+    finished_at: i64,
+    bet: i64,
+    payout: i64,
+}
+
+/// The last rounds `user` played a hand in that lasted to the end
+async fn recent_rounds(
+    State((_state, database)): State<AppState>,
+    Extension(user): Extension<User>,
+) -> impl IntoResponse {
+    let conn = database.lock().await;
+    let rounds = sqlx::query_as!(
+        RoundSummary,
+        r#"SELECT Rounds.id AS "id!", Rounds.room, Rounds.finished_at,
+            SUM(RoundHands.bet) AS "bet!: i64", SUM(RoundHands.payout) AS "payout!: i64"
+        FROM Rounds
+        JOIN RoundHands ON RoundHands.round_id = Rounds.id
+        WHERE RoundHands.user_id = ?
+        GROUP BY Rounds.id
+        ORDER BY Rounds.id DESC
+        LIMIT 50"#,
+        user.id
+    )
+    .fetch_all(&*conn)
+    .await
+    .unwrap();
+    drop(conn);
+    let mut context = tera::Context::new();
+    context.insert("rounds", &rounds);
+    Html(TERA.render("history.html", &context).unwrap())
+}
+
+#[derive(Serialize)]
+struct ResultRow {
+    seat: i64,
+    username: String,
+    second_hand: bool,
+    bet: i64,
+    result: String,
+    payout: i64,
+}
+
+#[derive(Deserialize)]
+struct ReplayQuery {
+    #[serde(default)]
+    speed: Option<u32>,
+}
+
+async fn replay_page(
+    Path(round): Path<i64>,
+    State((_state, database)): State<AppState>,
+    Extension(user): Extension<User>,
+) -> impl IntoResponse {
+    let conn = database.lock().await;
+    let room = sqlx::query_scalar!("SELECT room FROM Rounds WHERE id = ?", round)
+        .fetch_optional(&*conn)
+        .await
+        .unwrap();
+    let Some(room) = room else {
+        return error_404().await.into_response();
+    };
+    if !can_watch(&conn, round, &user).await {
+        return error_404().await.into_response();
+    }
+    let results = sqlx::query_as!(
+        ResultRow,
+        r#"SELECT RoundHands.seat, Users.username, RoundHands.second_hand AS "second_hand: bool",
+            RoundHands.bet, RoundHands.result, RoundHands.payout
+        FROM RoundHands
+        JOIN Users ON Users.id = RoundHands.user_id
+        WHERE RoundHands.round_id = ?
+        ORDER BY RoundHands.seat, RoundHands.second_hand"#,
+        round
+    )
+    .fetch_all(&*conn)
+    .await
+    .unwrap();
+    drop(conn);
+    let mut context = tera::Context::new();
+    context.insert("spectating", &true);
+    context.insert("id", &room);
+    context.insert("replay", &round);
+    context.insert("results", &results);
+    context.insert("speeds", &[1, 2, 4, MAX_SPEED]);
+    context.insert("min_bet", &0);
+    context.insert("max_bet", &0);
+    context.insert("chat_max_length", &chat::MAX_LENGTH);
+    Html(TERA.render("game.html", &context).unwrap()).into_response()
+}
+
+async fn replay_ws(
+    ws: Option<WebSocketUpgrade>,
+    Path(round): Path<i64>,
+    ConnectInfo(who): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(query): Query<ReplayQuery>,
+    State((_state, database)): State<AppState>,
+    Extension(user): Extension<User>,
+) -> impl IntoResponse {
+    let Some(ws) = ws else {
+        return error_400().into_response();
+    };
+    if !csrf::same_origin(&headers) {
+        return error_403().into_response();
+    }
+    let speed = query.speed.unwrap_or(1).clamp(1, MAX_SPEED);
+    let conn = database.lock().await;
+    if !can_watch(&conn, round, &user).await {
+        return error_404().await.into_response();
+    }
+    let events = sqlx::query!(
+        "SELECT after_ms, action FROM RoundEvents WHERE round_id = ? ORDER BY seq",
+        round
+    )
+    .fetch_all(&*conn)
+    .await
+    .unwrap();
+    drop(conn);
+    println!(
+        "{} ({who}) is replaying round {round} at {speed}x",
+        user.username
+    );
+    let events = events
+        .into_iter()
+        .map(|event| (event.after_ms, event.action))
+        .collect();
+    ws.on_upgrade(move |socket| replay(socket, events, speed))
+        .into_response()
+}
+
+/// Sends a round's events again, as far apart as they first were divided by
+/// `speed`
+async fn replay(mut socket: WebSocket, events: Vec<(i64, String)>, speed: u32) {
+    let start = tokio::time::Instant::now();
+    for (after, action) in events {
+        let after = Duration::from_millis(after.max(0) as u64) / speed;
+        tokio::time::sleep_until(start + after).await;
+        if socket.send(Message::Text(action)).await.is_err() {
+            return;
+        }
+    }
+    let _ = socket.send(Message::Close(None)).await;
+}
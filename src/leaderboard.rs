@@ -0,0 +1,184 @@
+//! Who is doing best, for everyone who hasn't opted out of being shown
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse},
+    Json,
+};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use tokio::sync::Mutex;
+
+use crate::{data::unix_time, routes::TERA, AppState};
+
+/// How many places each board shows
+const PLACES: i64 = 10;
+/// How long the boards are served before being worked out again
+const CACHE_FOR: Duration = Duration::from_secs(60);
+const WEEK: i64 = 7 * 24 * 60 * 60;
+const MONTH: i64 = 30 * 24 * 60 * 60;
+
+/// Boards, and when they were worked out
+type Cached = (Instant, Arc<Boards>);
+
+static CACHE: Lazy<Mutex<Option<Cached>>> = Lazy::new(|| Mutex::new(None));
+
+#[derive(Debug, Serialize)]
+pub struct Leader {
+    pub username: String,
+    pub name: String,
+    pub value: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Boards {
+    pub balance: Vec<Leader>,
+    /// Won or lost over the last 7 days
+    pub week: Vec<Leader>,
+    /// And over the last 30
+    pub month: Vec<Leader>,
+    /// The most hands won in a row
+    pub streak: Vec<Leader>,
+    pub blackjacks: Vec<Leader>,
+    pub updated_at: i64,
+}
+
+/// The boards, worked out again if the cached ones are too old. Holding the
+/// cache while they are means only one request does the work
+pub async fn boards(database: &Mutex<SqlitePool>) -> Arc<Boards> {
+    let mut cache = CACHE.lock().await;
+    if let Some((at, boards)) = &*cache {
+        if at.elapsed() < CACHE_FOR {
+            return boards.clone();
+        }
+    }
+    let conn = database.lock().await;
+    let conn = &*conn;
+    let boards = Arc::new(Boards {
+        balance: balance(conn).await,
+        week: net_since(conn, unix_time() - WEEK).await,
+        month: net_since(conn, unix_time() - MONTH).await,
+        streak: streak(conn).await,
+        blackjacks: blackjacks(conn).await,
+        updated_at: unix_time(),
+    });
+    *cache = Some((Instant::now(), boards.clone()));
+    boards
+}
+
+//Bots, banned users and anyone who has opted out are left off every board,
+//as are training tables which are played with play money
+
+async fn balance(conn: &SqlitePool) -> Vec<Leader> {
+    sqlx::query_as!(
+        Leader,
+        r#"SELECT username AS "username!", COALESCE(display_name, username) AS "name!: String",
+            balance AS "value!"
+        FROM Users
+        WHERE on_leaderboards AND NOT bot AND NOT banned
+        ORDER BY balance DESC, username
+        LIMIT ?"#,
+        PLACES
+    )
+    .fetch_all(conn)
+    .await
+    .unwrap()
+}
+
+async fn net_since(conn: &SqlitePool, since: i64) -> Vec<Leader> {
+    sqlx::query_as!(
+        Leader,
+        r#"SELECT Users.username AS "username!", COALESCE(Users.display_name, Users.username) AS "name!: String",
+            SUM(RoundHands.payout - RoundHands.bet) AS "value!: i64"
+        FROM RoundHands
+        JOIN Rounds ON Rounds.id = RoundHands.round_id
+        JOIN Users ON Users.id = RoundHands.user_id
+        WHERE Rounds.finished_at >= ? AND NOT Rounds.training
+            AND Users.on_leaderboards AND NOT Users.bot AND NOT Users.banned
+        GROUP BY Users.id
+        ORDER BY 3 DESC, Users.username
+        LIMIT ?"#,
+        since,
+        PLACES
+    )
+    .fetch_all(conn)
+    .await
+    .unwrap()
+}
+
+/// Pushes and losses both end a streak. Numbering each player's hands, then
+/// again separately for wins and everything else, gives every run of wins
+/// its own difference between the two
+async fn streak(conn: &SqlitePool) -> Vec<Leader> {
+    sqlx::query_as!(
+        Leader,
+        r#"WITH Hands AS (
+            SELECT RoundHands.user_id,
+                RoundHands.result IN ('Win', 'Blackjack') AS won,
+                ROW_NUMBER() OVER (
+                    PARTITION BY RoundHands.user_id
+                    ORDER BY RoundHands.round_id, RoundHands.hand
+                ) - ROW_NUMBER() OVER (
+                    PARTITION BY RoundHands.user_id, RoundHands.result IN ('Win', 'Blackjack')
+                    ORDER BY RoundHands.round_id, RoundHands.hand
+                ) AS run
+            FROM RoundHands
+            JOIN Rounds ON Rounds.id = RoundHands.round_id
+            WHERE NOT Rounds.training
+        ), Streaks AS (
+            SELECT user_id, COUNT(*) AS length
+            FROM Hands
+            WHERE won
+            GROUP BY user_id, run
+        )
+        SELECT Users.username AS "username!", COALESCE(Users.display_name, Users.username) AS "name!: String",
+            MAX(Streaks.length) AS "value!: i64"
+        FROM Streaks
+        JOIN Users ON Users.id = Streaks.user_id
+        WHERE Users.on_leaderboards AND NOT Users.bot AND NOT Users.banned
+        GROUP BY Users.id
+        ORDER BY 3 DESC, Users.username
+        LIMIT ?"#,
+        PLACES
+    )
+    .fetch_all(conn)
+    .await
+    .unwrap()
+}
+
+async fn blackjacks(conn: &SqlitePool) -> Vec<Leader> {
+    sqlx::query_as!(
+        Leader,
+        r#"SELECT Users.username AS "username!", COALESCE(Users.display_name, Users.username) AS "name!: String",
+            COUNT(*) AS "value!: i64"
+        FROM RoundHands
+        JOIN Rounds ON Rounds.id = RoundHands.round_id
+        JOIN Users ON Users.id = RoundHands.user_id
+        WHERE RoundHands.result = 'Blackjack' AND NOT Rounds.training
+            AND Users.on_leaderboards AND NOT Users.bot AND NOT Users.banned
+        GROUP BY Users.id
+        ORDER BY 3 DESC, Users.username
+        LIMIT ?"#,
+        PLACES
+    )
+    .fetch_all(conn)
+    .await
+    .unwrap()
+}
+
+pub async fn leaderboards(State((_state, database)): State<AppState>) -> impl IntoResponse {
+    let boards = boards(&database).await;
+    let mut context = tera::Context::new();
+    context.insert("boards", &*boards);
+    Html(TERA.render("leaderboards.html", &context).unwrap())
+}
+
+pub async fn leaderboards_json(State((_state, database)): State<AppState>) -> impl IntoResponse {
+    Json(boards(&database).await)
+}
@@ -0,0 +1,125 @@
+use serde::Serialize;
+use sqlx::SqlitePool;
+
+use crate::{
+    count::Count,
+    data::{unix_time, RoomId},
+};
+
+/// Why a balance changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum LedgerKind {
+    Bet,
+    Payout,
+    Refund,
+    Adjustment,
+    /// Given once a day, away from the tables
+    Bonus,
+    /// Topping up a balance too low to bet with
+    Rebuy,
+}
+
+/// A change to a user's balance. Nothing touches `Users.balance` except
+/// through [`Entry::apply`], so the ledger always accounts for it
+pub struct Entry {
+    user_id: i64,
+    amount: i64,
+    kind: LedgerKind,
+    room: Option<String>,
+    note: Option<String>,
+    actor_id: Option<i64>,
+    count: Option<Count>,
+}
+
+impl Entry {
+    pub fn new(user_id: i64, amount: i64, kind: LedgerKind) -> Self {
+        Self {
+            user_id,
+            amount,
+            kind,
+            room: None,
+            note: None,
+            actor_id: None,
+            count: None,
+        }
+    }
+
+    pub fn room(mut self, room: &RoomId) -> Self {
+        self.room = Some(room.to_string());
+        self
+    }
+
+    pub fn note(mut self, note: impl Into<String>) -> Self {
+        self.note = Some(note.into());
+        self
+    }
+
+    /// The staff member responsible for the change, if it wasn't the game
+    pub fn actor(mut self, actor_id: i64) -> Self {
+        self.actor_id = Some(actor_id);
+        self
+    }
+
+    pub fn amount(&self) -> i64 {
+        self.amount
+    }
+
+    /// The shoe's count when a bet was placed, to spot counters by
+    pub fn count(mut self, count: Count) -> Self {
+        self.count = Some(count);
+        self
+    }
+
+    /// Updates the balance and records the entry, returning the new balance
+    pub async fn apply(self, conn: &SqlitePool) -> i64 {
+        self.record(conn, false).await.unwrap()
+    }
+
+    /// Like [`Entry::apply`], but changes nothing and returns `None` if the
+    /// balance would end up below 0
+    pub async fn apply_unless_overdrawn(self, conn: &SqlitePool) -> Option<i64> {
+        self.record(conn, true).await
+    }
+
+    async fn record(self, conn: &SqlitePool, no_overdraft: bool) -> Option<i64> {
+        let now = unix_time();
+        let running_count = self.count.map(|count| count.running);
+        let true_count = self.count.map(|count| count.true_count);
+        let mut transaction = conn.begin().await.unwrap();
+        let balance = sqlx::query_scalar!(
+            r#"UPDATE Users
+            SET balance = balance + ?
+            WHERE id = ? AND (balance + ? >= 0 OR NOT ?)
+            RETURNING balance AS "balance!""#,
+            self.amount,
+            self.user_id,
+            self.amount,
+            no_overdraft
+        )
+        .fetch_optional(&mut transaction)
+        .await
+        .unwrap()?;
+        sqlx::query!(
+            "INSERT INTO Ledger (user_id, amount, balance_after, kind, room, note, actor_id,
+                running_count, true_count, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            self.user_id,
+            self.amount,
+            balance,
+            self.kind,
+            self.room,
+            self.note,
+            self.actor_id,
+            running_count,
+            true_count,
+            now
+        )
+        .execute(&mut transaction)
+        .await
+        .unwrap();
+        transaction.commit().await.unwrap();
+        Some(balance)
+    }
+}
@@ -0,0 +1,7 @@
+//! The game itself, shared by the server and the offline tools in `src/bin`
+
+pub mod card;
+pub mod count;
+pub mod engine;
+pub mod odds;
+pub mod strategy;
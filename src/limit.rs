@@ -0,0 +1,130 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::ConnectInfo,
+    http::Request,
+    response::{IntoResponse, Response},
+};
+use futures::future::{self, Either, Ready};
+use tower::{Layer, Service};
+
+use crate::routes;
+
+/// Once this many keys are tracked, buckets that have refilled are dropped
+const PRUNE_THRESHOLD: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// A token bucket per key, shared by every clone
+#[derive(Clone)]
+pub struct Buckets<K> {
+    burst: u32,
+    refill: Duration,
+    buckets: Arc<Mutex<HashMap<K, Bucket>>>,
+}
+
+impl<K: Eq + Hash> Buckets<K> {
+    /// Allows `burst` at once, then one more every `refill`
+    pub fn new(burst: u32, refill: Duration) -> Self {
+        Self {
+            burst,
+            refill,
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn try_acquire(&self, key: K) -> bool {
+        let now = Instant::now();
+        let burst = f64::from(self.burst);
+        let refill = self.refill.as_secs_f64();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.last_refill).as_secs_f64() / refill
+                    < burst
+            });
+        }
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: burst,
+            last_refill: now,
+        });
+        let refilled = now.duration_since(bucket.last_refill).as_secs_f64() / refill;
+        bucket.tokens = (bucket.tokens + refilled).min(burst);
+        bucket.last_refill = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Limits requests per client address
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    buckets: Buckets<IpAddr>,
+}
+
+impl RateLimitLayer {
+    /// Allows `burst` requests at once, then one more every `refill`
+    pub fn new(burst: u32, refill: Duration) -> Self {
+        Self {
+            buckets: Buckets::new(burst, refill),
+        }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit {
+            inner,
+            limit: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    limit: RateLimitLayer,
+}
+
+impl<S, B> Service<Request<B>> for RateLimit<S>
+where
+    S: Service<Request<B>, Response = Response>,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Either<Ready<Result<Response, S::Error>>, S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let who = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(who)| *who);
+        if let Some(who) = who {
+            if !self.limit.buckets.try_acquire(who.ip()) {
+                println!("{who} is being rate limited on {}", request.uri());
+                return Either::Left(future::ok(routes::error_429().into_response()));
+            }
+        }
+        Either::Right(self.inner.call(request))
+    }
+}
@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket},
+        State, WebSocketUpgrade,
+    },
+    http::HeaderMap,
+    response::{Html, IntoResponse},
+    Extension, Json,
+};
+use serde::Serialize;
+use tokio::sync::{broadcast::error::RecvError, Mutex};
+
+use crate::{
+    csrf,
+    data::{MyState, Phase, Room},
+    engine::TableRules,
+    routes::{error_403, TERA},
+    AppState, User,
+};
+
+/// What the lobby shows about a room
+#[derive(Debug, Clone, Serialize)]
+pub struct RoomListing {
+    pub id: String,
+    pub seats: usize,
+    pub max_seats: usize,
+    pub phase: Phase,
+    pub joinable: bool,
+    pub spectators: usize,
+    pub can_spectate: bool,
+    pub rules: TableRules,
+    pub summary: String,
+}
+
+impl RoomListing {
+    pub fn new(room: &Room) -> Self {
+        Self {
+            id: room.id.to_string(),
+            seats: room.taken_seats().len(),
+            max_seats: room.rules.max_players,
+            phase: room.phase(),
+            joinable: room.is_joinable(),
+            spectators: room.spectators.len(),
+            can_spectate: room.can_spectate(),
+            rules: room.rules.clone(),
+            summary: room.rules.summary(),
+        }
+    }
+}
+
+/// Sent to everyone watching the lobby
+#[derive(Debug, Clone, Serialize)]
+pub enum LobbyEvent {
+    /// Every room, sent when first connecting
+    Rooms(Vec<RoomListing>),
+    /// A room was created or changed
+    Updated(RoomListing),
+    Removed {
+        id: String,
+    },
+}
+
+pub async fn lobby(Extension(user): Extension<User>) -> impl IntoResponse {
+    let mut context = tera::Context::new();
+    context.insert("balance", &user.balance);
+    Html(TERA.render("lobby.html", &context).unwrap())
+}
+
+pub async fn rooms(State((state, _database)): State<AppState>) -> impl IntoResponse {
+    Json(state.lock().await.listings())
+}
+
+pub async fn lobby_ws(
+    ws: WebSocketUpgrade,
+    headers: HeaderMap,
+    State((state, _database)): State<AppState>,
+    Extension(user): Extension<User>,
+) -> impl IntoResponse {
+    if !csrf::same_origin(&headers) {
+        println!(
+            "{} tried to open the lobby from another origin",
+            user.username
+        );
+        return error_403().into_response();
+    }
+    ws.on_upgrade(move |socket| watch_lobby(socket, state))
+        .into_response()
+}
+
+async fn watch_lobby(mut socket: WebSocket, state: Arc<Mutex<MyState>>) {
+    let (rooms, mut events) = {
+        let lock = state.lock().await;
+        (lock.listings(), lock.lobby.subscribe())
+    };
+    if send(&mut socket, &LobbyEvent::Rooms(rooms)).await.is_err() {
+        return;
+    }
+    loop {
+        let event = tokio::select! {
+            event = events.recv() => event,
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => continue,
+            },
+        };
+        let event = match event {
+            Ok(event) => event,
+            //We missed some updates, so start over from a full listing
+            Err(RecvError::Lagged(_)) => LobbyEvent::Rooms(state.lock().await.listings()),
+            Err(RecvError::Closed) => return,
+        };
+        if send(&mut socket, &event).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn send(socket: &mut WebSocket, event: &LobbyEvent) -> Result<(), axum::Error> {
+    let msg = serde_json::to_string(event).unwrap();
+    socket.send(Message::Text(msg)).await
+}
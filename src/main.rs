@@ -1,15 +1,16 @@
-use crate::{card::Rank, data::Room};
+use crate::{access::Visibility, data::Room};
 use std::{
     net::{Ipv4Addr, SocketAddr},
     sync::Arc,
     time::Duration,
 };
 
-use data::{MyState, RoomId, Score};
+use data::{HandId, MyState, Phase, RoomId, SeenHand};
 use serde::{Deserialize, Serialize};
 
 use axum::{
     extract::ws::{Message, WebSocket},
+    handler::Handler,
     http::StatusCode,
     response::IntoResponse,
     response::Redirect,
@@ -26,21 +27,58 @@ use axum_login::{
 };
 use futures::{
     sink::SinkExt,
-    stream::{SplitSink, StreamExt},
+    stream::{SplitSink, SplitStream, StreamExt},
 };
 use sqlx::SqlitePool;
 use tokio::sync::Mutex;
 use tower::builder::ServiceBuilder;
 use tower_http::catch_panic::CatchPanicLayer;
 
-mod card;
-use card::Card;
+mod access;
+mod admin;
+mod auth;
+mod bots;
+use bots::BotKind;
+use prototype1::{
+    card::{self, Card},
+    count,
+    engine::{self, GameResult},
+    odds,
+    strategy::{self, Advice, Decision},
+};
+mod chat;
+mod counters;
+use chat::ChatMessage;
+mod csrf;
 
 mod data;
+mod economy;
+mod hex;
+mod history;
 use data::Hand;
+use history::{HandRecord, Move};
+mod leaderboard;
+mod ledger;
+use ledger::{Entry, LedgerKind};
+mod limit;
+mod lobby;
+mod moderation;
+use limit::RateLimitLayer;
+mod profile;
+use profile::{Avatar, PlayerInfo};
 mod routes;
+mod stats;
+mod training;
+use training::TrainingStats;
 
 type Who = SocketAddr;
+
+/// How a connection takes part in a room
+pub enum Joining {
+    /// Sit down, in the given seat if it's free
+    Seat(Option<usize>),
+    Spectate,
+}
 type Socket = SplitSink<WebSocket, Message>;
 
 #[derive(Debug, Clone, sqlx::FromRow)]
@@ -49,64 +87,115 @@ pub struct User {
     username: String,
     password: String,
     balance: i64,
+    role: Role,
+    banned: bool,
+    display_name: Option<String>,
+    avatar: Avatar,
+    on_leaderboards: bool,
+}
+
+/// What a user is allowed to do, in increasing order of privilege
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type,
+)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum Role {
+    Player,
+    Moderator,
+    Admin,
 }
 
-impl AuthUser<i64, ()> for User {
+impl AuthUser<i64, Role> for User {
     fn get_id(&self) -> i64 {
         self.id
     }
 
+    //`password` only ever holds an Argon2 hash once the user has logged in
     fn get_password_hash(&self) -> SecretVec<u8> {
         SecretVec::new(self.password.clone().into())
     }
 
-    fn get_role(&self) -> Option<()> {
-        None
+    fn get_role(&self) -> Option<Role> {
+        Some(self.role)
     }
 }
 
-type Auth = AuthContext<i64, User, SqliteStore<User>, ()>;
+type Auth = AuthContext<i64, User, SqliteStore<User, Role>, Role>;
+type AppState = (Arc<Mutex<MyState>>, Arc<Mutex<SqlitePool>>);
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     routes::template_force();
+    economy::economy_force();
     let secret = std::array::from_fn::<u8, 64, _>(|_| fastrand::u8(0..u8::MAX));
     let session_store = SessionMemoryStore::new();
     let session_layer = SessionLayer::new(session_store, &secret);
     let connection = SqlitePool::connect("sqlite://database").await.unwrap();
 
-    sqlx::query!(
-        "CREATE TABLE IF NOT EXISTS Users (
-            id int NOT NULL UNIQUE PRIMARY KEY,
-            username varchar(255) NOT NULL UNIQUE,
-            password varchar(255) NOT NULL,
-            balance int NOT NULL
-        )"
-    )
-    .execute(&connection)
-    .await?;
-
-    sqlx::query!(
-        "UPDATE Users
-        SET balance = 5000"
-    )
-    .execute(&connection)
-    .await?;
+    sqlx::migrate!().run(&connection).await?;
+    auth::bootstrap_admin(&connection).await;
 
     let database = Arc::new(Mutex::new(connection.clone()));
-    let sqlite_store = SqliteStore::<User>::new(connection);
+    //Banned users are treated as though they don't exist, which also ends their
+    //sessions. Nobody can log in as a bot either
+    let sqlite_store = SqliteStore::<User, Role>::new(connection)
+        .with_query("SELECT * FROM Users WHERE id = $1 AND NOT banned AND NOT bot");
     let auth_layer = AuthLayer::new(sqlite_store, &secret);
 
     let state = Arc::new(Mutex::new(data::MyState::new()));
     let assets = SpaRouter::new("/static", "static");
     let app = Router::new()
-        .route("/create", post(routes::create_room))
-        .route("/:id", get(routes::ingame))
-        .route("/:id/ws", get(routes::ws_handler))
+        .route(
+            "/create",
+            post(routes::create_room).layer(RateLimitLayer::new(5, Duration::from_secs(60))),
+        )
+        .route(
+            "/:id",
+            get(routes::ingame).post(
+                routes::recieve_room_password
+                    .layer(RateLimitLayer::new(5, Duration::from_secs(30))),
+            ),
+        )
+        .route(
+            "/:id/ws",
+            get(routes::ws_handler).layer(RateLimitLayer::new(10, Duration::from_secs(6))),
+        )
         .route("/", get(routes::home))
-        .route("/logout", post(routes::logout).get(routes::logout))
-        .route_layer(RequireAuthorizationLayer::<i64, User, ()>::login())
-        .route("/login", get(routes::login).post(routes::recieve_login))
+        .route("/lobby", get(lobby::lobby))
+        .route(
+            "/lobby/ws",
+            get(lobby::lobby_ws).layer(RateLimitLayer::new(10, Duration::from_secs(6))),
+        )
+        .route("/api/rooms", get(lobby::rooms))
+        .route("/leaderboards", get(leaderboard::leaderboards))
+        .route("/api/leaderboards", get(leaderboard::leaderboards_json))
+        .route(
+            "/settings",
+            get(profile::settings).post(profile::recieve_settings),
+        )
+        .route("/profile", get(profile::profile))
+        .route("/profile/balance", get(profile::balance_history))
+        .route(
+            "/rebuy",
+            post(economy::rebuy).layer(RateLimitLayer::new(3, Duration::from_secs(60))),
+        )
+        .route("/logout", post(routes::logout))
+        .route_layer(RequireAuthorizationLayer::<i64, User, Role>::login())
+        .route(
+            "/login",
+            get(routes::login)
+                .post(routes::recieve_login.layer(RateLimitLayer::new(10, Duration::from_secs(6)))),
+        )
+        .route(
+            "/register",
+            get(routes::register).post(
+                routes::recieve_register.layer(RateLimitLayer::new(5, Duration::from_secs(60))),
+            ),
+        )
+        .nest("/admin", admin::router())
+        .nest("/moderation", moderation::router())
+        .nest("/history", history::router())
         .with_state((state, database))
         .merge(assets)
         .layer(
@@ -139,6 +228,7 @@ async fn websocket(
     id: RoomId,
     state: Arc<Mutex<MyState>>,
     user: User,
+    joining: Joining,
 ) {
     let Ok(_) = socket.send(Message::Ping(vec![1, 2, 3, 4, 5, 6])).await else {
         println!("Could not send ping to {} ({who})", user.username);
@@ -147,25 +237,46 @@ async fn websocket(
 
     println!("Pinged {} ({who})", user.username);
 
-    let (mut sender, mut socket) = socket.split();
+    let (sender, mut socket) = socket.split();
+
+    let wanted = match joining {
+        Joining::Seat(wanted) => wanted,
+        Joining::Spectate => {
+            spectate(sender, socket, who, &id, &state, &user).await;
+            return;
+        }
+    };
 
     {
         let lock = &mut state.lock().await.rooms;
         let room = lock.get_mut(&id).unwrap();
 
-        if room.sockets.len() == 0 {
-            let msg = serde_json::to_string(&ServerAction::NewHost).unwrap();
-            let Ok(_) = sender.send(Message::Text(msg)).await else {
-                println!("Failed to send message to {who}");
-                return;
-            };
-        }
-        room.hands.push(Hand::new(who, Vec::new(), false, user.id));
+        //Someone may have sat down since the page loaded
+        let Some(seat) = room.free_seat(wanted).filter(|_| room.is_joinable()) else {
+            println!("{} ({who}) lost their seat in {id}", user.username);
+            let mut sender = sender;
+            let _ = sender.send(Message::Close(None)).await;
+            return;
+        };
+        let hand = room.take_seat(who, seat, user.id);
         room.sockets.insert(who, sender);
+        room.players.insert(who, user.clone());
         let action = ServerAction::PlayerJoin {
-            player: room.sockets.len(),
+            seat,
+            hand,
+            player: PlayerInfo::from(&user),
         };
         room.notify_all(&action).await;
+        let action = ServerAction::YourSeats { seats: vec![seat] };
+        room.notify_player(who, &action).await;
+        //Catch them up on who's hosting, how many are watching and so on
+        let action = room.table_state();
+        room.notify_player(who, &action).await;
+        if room.host.is_none() {
+            println!("{} ({who}) is now the host of {id}", user.username);
+            room.set_host(who).await;
+        }
+        room.publish();
     }
 
     loop {
@@ -182,82 +293,196 @@ async fn websocket(
             }
         };
 
+        //Staff may have closed the room or kicked us while we were waiting
+        let still_seated = state
+            .lock()
+            .await
+            .rooms
+            .get(&id)
+            .is_some_and(|room| room.sockets.contains(&who));
+        if !still_seated {
+            println!("{who} is no longer in room {id}");
+            return;
+        }
+
         match msg {
             Message::Text(msg) => match serde_json::from_str(&msg) {
                 Ok(PlayerAction::GameStart) => start_game(&state, &id, who).await,
+                Ok(PlayerAction::Kick { seat }) => kick_player(&state, &id, who, seat).await,
+                Ok(PlayerAction::TransferHost { seat }) => {
+                    transfer_host(&state, &id, who, seat).await
+                }
+                Ok(PlayerAction::TakeSeat { seat }) => take_seat(&state, &id, who, seat).await,
+                Ok(PlayerAction::ChangeSeat { from, to }) => {
+                    change_seat(&state, &id, who, from, to).await
+                }
+                Ok(PlayerAction::LeaveSeat { seat }) => leave_seat(&state, &id, who, seat).await,
+                Ok(PlayerAction::LockTable { locked }) => {
+                    lock_table(&state, &id, who, locked).await
+                }
                 Ok(PlayerAction::EndTurn) => end_turn(&state, &id, who).await,
                 Ok(PlayerAction::Deal) => deal(&state, &id, who).await,
-                Ok(PlayerAction::Split) => split(&state, &id, who, user.id).await,
+                Ok(PlayerAction::Split) => {
+                    split(&state, &id, who, user.id).await;
+                }
                 Ok(PlayerAction::Bet { amount }) => {
+                    place_bet(&state, &id, who, &user, amount).await;
+                }
+                Ok(PlayerAction::Hint) => hint(&state, &id, who).await,
+                Ok(PlayerAction::CountAnswer { count }) => {
+                    answer_count(&state, &id, who, count).await
+                }
+                Ok(PlayerAction::EndTraining) => end_training(&state, &id, who).await,
+                Ok(PlayerAction::AddBot { seat, strategy }) => {
+                    add_bot(&state, &id, who, seat, strategy).await
+                }
+                Ok(PlayerAction::Chat { text }) => chat::send(&state, &id, who, &user, &text).await,
+                Ok(PlayerAction::ReportChat { id: message }) => {
+                    chat::report(&state, &id, who, &user, message).await
+                }
+                Ok(PlayerAction::MuteChat { seat, minutes }) => {
+                    chat::mute_seat(&state, &id, who, &user, seat, minutes).await
+                }
+                Ok(PlayerAction::RevokeInvites) => {
                     let mut lock = state.lock().await;
                     let room = lock.rooms.get_mut(&id).unwrap();
-                    //TODO: Verify that it's your turn
-                    println!("{} ({who}) bet {}", user.username, amount);
-                    if amount > 0 {
-                        let hand = room.hands.iter_mut().find(|p| *p.who() == who).unwrap();
-                        hand.bet = amount;
-                    } else {
-                        println!("Bad bet amount");
+                    if room.access.owner != user.id || room.access.visibility != Visibility::Private
+                    {
+                        println!("{} ({who}) can't revoke invites for {id}", user.username);
+                        continue;
+                    }
+                    println!("{} ({who}) revoked the invites for {id}", user.username);
+                    //Whoever is already seated keeps their place
+                    let seated: Vec<i64> = room.players.values().map(|p| p.id).collect();
+                    room.access.revoke_invites(seated);
+                    let link = room.access.invite_link(&id);
+                    if let Some(socket) = room.sockets.get_mut(&who) {
+                        let msg =
+                            serde_json::to_string(&ServerAction::InviteLink { link }).unwrap();
+                        let _ = socket.send(Message::Text(msg)).await;
                     }
-                    let database = room.database().lock_owned().await;
-                    sqlx::query!(
-                        "UPDATE Users
-                        SET balance = balance - ?
-                        WHERE id = ?",
-                        amount,
-                        user.id
-                    )
-                    .execute(&*database)
-                    .await
-                    .unwrap();
-                    drop(database);
-                    let current = room.current();
-                    let can_split = !current.is_second()
-                        && current.hand[0].score_card() == current.hand[1].score_card();
-                    let action = ServerAction::YourTurn { can_split };
-                    println!("It is now {}'s turn", current.who());
-                    room.notify_current(&action).await;
                 }
                 Err(_) => println!("{who} sent an invalid action: {msg}"),
             },
             Message::Pong(_) => println!("Recieved pong from {who}"),
             Message::Close(_) => {
                 leave(&state, &id, who).await;
+                bots::play(&state, &id).await;
                 return;
             }
             _ => println!("Unknown message {msg:?}"),
         }
+        //Whatever just happened may have passed the turn to a bot
+        bots::play(&state, &id).await;
     }
 }
 
-async fn start_game(state: &Arc<Mutex<MyState>>, id: &RoomId, _who: Who) {
-    //TODO: Validation
+/// Lets `user` watch the room without taking a seat. Anything they send other
+/// than closing the connection is ignored
+async fn spectate(
+    mut sender: Socket,
+    mut socket: SplitStream<WebSocket>,
+    who: Who,
+    id: &RoomId,
+    state: &Arc<Mutex<MyState>>,
+    user: &User,
+) {
+    {
+        let mut lock = state.lock().await;
+        let Some(room) = lock.rooms.get_mut(id) else {
+            return;
+        };
+        if !room.can_spectate() {
+            println!(
+                "{} ({who}) can't watch {id}, it has too many spectators",
+                user.username
+            );
+            let _ = sender.send(Message::Close(None)).await;
+            return;
+        }
+        let msg = serde_json::to_string(&room.table_state()).unwrap();
+        let Ok(_) = sender.send(Message::Text(msg)).await else {
+            println!("Failed to send message to {who}");
+            return;
+        };
+        room.spectators.insert(who, sender);
+        let action = ServerAction::Spectators {
+            count: room.spectators.len(),
+        };
+        room.notify_all(&action).await;
+        room.publish();
+    }
+    println!("{} ({who}) is spectating {id}", user.username);
+
+    loop {
+        match socket.next().await {
+            Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+            Some(Ok(Message::Text(msg))) => {
+                println!(
+                    "{} ({who}) tried to play while spectating: {msg}",
+                    user.username
+                )
+            }
+            Some(Ok(_)) => {}
+        }
+    }
+
+    println!("{} ({who}) stopped spectating {id}", user.username);
+    let mut lock = state.lock().await;
+    let Some(room) = lock.rooms.get_mut(id) else {
+        return;
+    };
+    if room.spectators.remove(&who).is_none() {
+        return;
+    }
+    let action = ServerAction::Spectators {
+        count: room.spectators.len(),
+    };
+    room.notify_all(&action).await;
+    room.publish();
+}
+
+async fn start_game(state: &Arc<Mutex<MyState>>, id: &RoomId, who: Who) {
     let mut lock = state.lock().await;
     let room = lock.rooms.get_mut(id).unwrap();
+    if !room.is_host(who) {
+        println!("{who} tried to start {id} without being the host");
+        return;
+    }
+    if room.phase() == Phase::Playing {
+        println!("{who} tried to start {id} twice");
+        return;
+    }
+    if room.finished {
+        room.reset_round();
+        let action = ServerAction::NewRound;
+        room.notify_all(&action).await;
+    }
     room.started = true;
+    room.start_log();
+    room.publish();
     let mut cards = vec![];
-    for (index, _hand) in room.hands.iter().enumerate() {
+    let hands = room.hands.iter().map(Hand::id).collect::<Vec<_>>();
+    for hand in hands {
         let card1 = room.decks.pop().unwrap();
         let card2 = room.decks.pop().unwrap();
         let action = ServerAction::Dealt {
-            hand: index,
+            hand,
             card: Some(card1),
-            second_hand: false,
         };
-        room.sockets.notify(&action).await;
+        room.notify_all(&action).await;
         let action = ServerAction::Dealt {
-            hand: index,
+            hand,
             card: Some(card2),
-            second_hand: false,
         };
-        room.sockets.notify(&action).await;
+        room.notify_all(&action).await;
         cards.push([card1, card2]);
     }
 
     room.hands
         .iter_mut()
-        .zip(cards.into_iter())
-        .for_each(|(hand, new_cards)| hand.hand.extend_from_slice(&new_cards));
+        .zip(cards)
+        .for_each(|(hand, new_cards)| hand.hand.cards.extend_from_slice(&new_cards));
 
     let cards = room.decks.split_off(room.decks.len() - 2);
     let action = ServerAction::DealDealer { card: None };
@@ -269,18 +494,283 @@ async fn start_game(state: &Arc<Mutex<MyState>>, id: &RoomId, _who: Who) {
     room.dealer_hand.extend_from_slice(&cards);
     //TODO: End game if dealer has blackjack?
 
-    let action = ServerAction::RequestBet;
+    room.first_turn();
+    let action = ServerAction::RequestBet {
+        hand: room.current().id(),
+    };
+    room.notify_current(&action).await;
+}
+
+/// Checks and places a bet on the current hand, returning whether it was
+/// taken. Otherwise the player is asked to bet again
+async fn place_bet(
+    state: &Arc<Mutex<MyState>>,
+    id: &RoomId,
+    who: Who,
+    user: &User,
+    amount: u32,
+) -> bool {
+    let mut lock = state.lock().await;
+    let room = lock.rooms.get_mut(id).unwrap();
+    if !room.is_turn_of(who) || room.current().bet != 0 {
+        println!("{} ({who}) bet out of turn", user.username);
+        return false;
+    }
+    println!("{} ({who}) bet {}", user.username, amount);
+    let balance = room.balance(user.id).await;
+    let rules = &room.rules;
+    if amount < rules.min_bet || amount > rules.max_bet || i64::from(amount) > balance {
+        println!("Bad bet amount");
+        let action = ServerAction::RequestBet {
+            hand: room.current().id(),
+        };
+        room.notify_current(&action).await;
+        return false;
+    }
+    room.current_mut().bet = amount;
+    let action = ServerAction::BetPlaced {
+        hand: room.current().id(),
+        amount,
+    };
+    room.notify_all(&action).await;
+    room.log(Move::Bet, Some(amount), None);
+    let entry = Entry::new(user.id, -i64::from(amount), LedgerKind::Bet)
+        .room(id)
+        .count(room.count());
+    room.apply(entry).await;
+    let current = room.current();
+    let action = ServerAction::YourTurn {
+        hand: current.id(),
+        can_split: current.hand.can_split(),
+    };
+    println!("It is now {}'s turn", current.who());
     room.notify_current(&action).await;
+    true
+}
+
+/// Checks a training player's answer when asked for the running count
+async fn answer_count(state: &Arc<Mutex<MyState>>, id: &RoomId, who: Who, count: i32) {
+    let mut lock = state.lock().await;
+    let room = lock.rooms.get_mut(id).unwrap();
+    let Some((correct, running)) = room
+        .training
+        .as_mut()
+        .and_then(|training| training.answer_count(count))
+    else {
+        println!("{who} answered a count nobody asked for");
+        return;
+    };
+    let action = ServerAction::CountChecked {
+        correct,
+        count: running,
+    };
+    room.notify_player(who, &action).await;
+}
+
+/// Wraps up a training session with how it went
+async fn end_training(state: &Arc<Mutex<MyState>>, id: &RoomId, who: Who) {
+    let mut lock = state.lock().await;
+    let room = lock.rooms.get_mut(id).unwrap();
+    let Some(training) = &room.training else {
+        println!("{who} tried to end training in {id}, which isn't a training room");
+        return;
+    };
+    let stats = training.stats();
+    println!("{who} finished training in {id}: {stats:?}");
+    let action = ServerAction::TrainingReport { stats, over: true };
+    room.disconnect(who, &action).await;
+}
+
+/// Tells `who` what basic strategy would do with their hand, if the table
+/// allows hints
+async fn hint(state: &Arc<Mutex<MyState>>, id: &RoomId, who: Who) {
+    let mut lock = state.lock().await;
+    let room = lock.rooms.get_mut(id).unwrap();
+    if !room.rules.hints || !room.is_turn_of(who) || room.current().bet == 0 {
+        println!("{who} can't have a hint right now");
+        return;
+    }
+    let hint = strategy::advise(&room.current().hand, room.dealer_hand[1], &room.rules);
+    let action = ServerAction::Hint {
+        advice: hint.advice,
+        reason: hint.reason,
+    };
+    room.notify_player(who, &action).await;
+}
+
+/// Host only: sits a bot playing `strategy` down in a free seat
+async fn add_bot(
+    state: &Arc<Mutex<MyState>>,
+    id: &RoomId,
+    who: Who,
+    seat: usize,
+    strategy: BotKind,
+) {
+    let mut lock = state.lock().await;
+    let room = lock.rooms.get_mut(id).unwrap();
+    if !room.is_host(who) || !room.between_rounds() {
+        println!("{who} can't add a bot to {id}");
+        return;
+    }
+    if room.free_seat(Some(seat)) != Some(seat) {
+        println!("{who} tried to add a bot at seat {seat} in {id}, which isn't free");
+        return;
+    }
+    let database = room.database();
+    let username = strategy.username();
+    let conn = database.lock().await;
+    let user = sqlx::query_as!(
+        User,
+        r#"SELECT id AS "id!", username, password, balance, role AS "role: Role", banned,
+            display_name, avatar AS "avatar: Avatar", on_leaderboards
+        FROM Users
+        WHERE username = ? AND bot"#,
+        username,
+    )
+    .fetch_optional(&*conn)
+    .await
+    .unwrap();
+    let Some(user) = user else {
+        println!("There is no account for {strategy:?} bots to play with");
+        return;
+    };
+    economy::top_up_bot(&conn, &user).await;
+    drop(conn);
+    let player = PlayerInfo::from(&user);
+    let (bot, hand) = room.add_bot(strategy, user, seat);
+    println!("{who} added a {strategy:?} bot ({bot}) at seat {seat} in {id}");
+    let action = ServerAction::PlayerJoin { seat, hand, player };
+    room.notify_all(&action).await;
+    room.publish();
+}
+
+async fn kick_player(state: &Arc<Mutex<MyState>>, id: &RoomId, who: Who, seat: usize) {
+    let mut lock = state.lock().await;
+    let room = lock.rooms.get_mut(id).unwrap();
+    if !room.is_host(who) {
+        println!("{who} tried to kick someone from {id} without being the host");
+        return;
+    }
+    let Some(player) = room.seated_at(seat).filter(|&player| player != who) else {
+        println!("{who} tried to kick an invalid seat {seat} from {id}");
+        return;
+    };
+    println!("The host of {id} kicked {player}");
+    room.kick(player).await;
+}
+
+async fn transfer_host(state: &Arc<Mutex<MyState>>, id: &RoomId, who: Who, seat: usize) {
+    let mut lock = state.lock().await;
+    let room = lock.rooms.get_mut(id).unwrap();
+    if !room.is_host(who) {
+        println!("{who} tried to hand over {id} without being the host");
+        return;
+    }
+    let Some(player) = room.seated_at(seat).filter(|&player| player != who) else {
+        println!("{who} tried to make an invalid seat {seat} the host of {id}");
+        return;
+    };
+    println!("{who} made {player} the host of {id}");
+    room.set_host(player).await;
+}
+
+async fn take_seat(state: &Arc<Mutex<MyState>>, id: &RoomId, who: Who, seat: usize) {
+    let mut lock = state.lock().await;
+    let room = lock.rooms.get_mut(id).unwrap();
+    let seats = room.seats_of(who);
+    if !room.between_rounds() || seats.len() >= room.rules.max_seats_per_player {
+        println!("{who} can't take another seat in {id}");
+        return;
+    }
+    if room.free_seat(Some(seat)) != Some(seat) {
+        println!("{who} tried to take seat {seat} in {id}, which isn't free");
+        return;
+    }
+    let account_id = room.players[&who].id;
+    let hand = room.take_seat(who, seat, account_id);
+    println!("{who} also sat down at seat {seat} in {id}");
+    let player = PlayerInfo::from(&room.players[&who]);
+    let action = ServerAction::PlayerJoin { seat, hand, player };
+    room.notify_all(&action).await;
+    room.notify_seats(who).await;
+    room.publish();
+}
+
+/// Moves `who` from one of their seats to a free one
+async fn change_seat(state: &Arc<Mutex<MyState>>, id: &RoomId, who: Who, from: usize, to: usize) {
+    let mut lock = state.lock().await;
+    let room = lock.rooms.get_mut(id).unwrap();
+    if !room.between_rounds() || room.seated_at(from) != Some(who) {
+        println!("{who} can't move from seat {from} in {id}");
+        return;
+    }
+    if room.free_seat(Some(to)) != Some(to) {
+        println!("{who} tried to move to seat {to} in {id}, which isn't free");
+        return;
+    }
+    room.move_seat(from, to);
+    let hand = room.hand_at(to);
+    println!("{who} moved from seat {from} to {to} in {id}");
+    let action = ServerAction::PlayerLeave { seat: from };
+    room.notify_all(&action).await;
+    let player = PlayerInfo::from(&room.players[&who]);
+    let action = ServerAction::PlayerJoin {
+        seat: to,
+        hand,
+        player,
+    };
+    room.notify_all(&action).await;
+    room.notify_seats(who).await;
+    if room.is_host(who) {
+        room.notify_all(&ServerAction::HostChanged { seat: to })
+            .await;
+    }
 }
 
-async fn end_turn(state: &Arc<Mutex<MyState>>, id: &RoomId, _who: Who) {
+/// Gives up one of several seats. Leaving the last one means leaving the room
+async fn leave_seat(state: &Arc<Mutex<MyState>>, id: &RoomId, who: Who, seat: usize) {
     let mut lock = state.lock().await;
     let room = lock.rooms.get_mut(id).unwrap();
-    //TODO: Verify it's the player's turn
-    // if !room.players.is_current(|p| p.who == who) {
-    //     println!("{who} sent their turn out of order!");
-    //     continue;
-    // }
+    let seats = room.seats_of(who);
+    if !room.between_rounds() || seats.len() < 2 || !seats.contains(&seat) {
+        println!("{who} can't give up seat {seat} in {id}");
+        return;
+    }
+    room.leave_seat(seat);
+    println!("{who} gave up seat {seat} in {id}");
+    let action = ServerAction::PlayerLeave { seat };
+    room.notify_all(&action).await;
+    room.notify_seats(who).await;
+    if room.is_host(who) {
+        let seat = room.seats_of(who)[0];
+        room.notify_all(&ServerAction::HostChanged { seat }).await;
+    }
+    room.publish();
+}
+
+async fn lock_table(state: &Arc<Mutex<MyState>>, id: &RoomId, who: Who, locked: bool) {
+    let mut lock = state.lock().await;
+    let room = lock.rooms.get_mut(id).unwrap();
+    if !room.is_host(who) {
+        println!("{who} tried to lock {id} without being the host");
+        return;
+    }
+    println!("{who} set {id} locked: {locked}");
+    room.locked = locked;
+    let action = ServerAction::TableLocked { locked };
+    room.notify_all(&action).await;
+    room.publish();
+}
+
+async fn end_turn(state: &Arc<Mutex<MyState>>, id: &RoomId, who: Who) {
+    let mut lock = state.lock().await;
+    let room = lock.rooms.get_mut(id).unwrap();
+    if !room.is_turn_of(who) || room.current().bet == 0 {
+        println!("{who} sent their turn out of order!");
+        return;
+    }
+    room.grade(Decision::Stand).await;
+    room.log(Move::Stand, None, None);
     let was_last_player = room.next_hand();
     if was_last_player {
         println!("Game is over");
@@ -289,12 +779,16 @@ async fn end_turn(state: &Arc<Mutex<MyState>>, id: &RoomId, _who: Who) {
     }
     let current = room.current();
     let who = *current.who();
+    let hand = current.id();
     if !current.is_second() {
-        let action = ServerAction::RequestBet;
+        let action = ServerAction::RequestBet { hand };
         room.notify_current(&action).await;
     } else {
-        let stop = current.hand[0].rank == Rank::Ace;
-        let action = ServerAction::YourTurn { can_split: false };
+        let stop = current.hand.is_split_aces();
+        let action = ServerAction::YourTurn {
+            hand,
+            can_split: false,
+        };
         room.notify_current(&action).await;
         if stop {
             let action = ServerAction::EndTurn;
@@ -308,274 +802,492 @@ async fn deal(state: &Arc<Mutex<MyState>>, id: &RoomId, who: Who) {
     println!("{who} has requested a deal");
     let mut lock = state.lock().await;
     let room = lock.rooms.get_mut(id).unwrap();
-    //TODO: Verify it's the players turn
-    // if !room.players.is_current(|p| p.who == who) {
-    //     println!("{who} sent their turn out of order!");
-    //     continue;
-    // }
+    if !room.is_turn_of(who) || room.current().bet == 0 || room.current().score().is_bust() {
+        println!("{who} sent their turn out of order!");
+        return;
+    }
+    room.grade(Decision::Hit).await;
 
     let card = room.decks.pop().unwrap();
-    room.current_mut().hand.push(card);
-    let second = room.current().is_second();
-    let hand = room.find_first_hand(room.current());
+    room.log(Move::Hit, None, Some(card));
+    room.current_mut().hand.cards.push(card);
     let action = ServerAction::Dealt {
-        hand,
+        hand: room.current().id(),
         card: Some(card),
-        second_hand: second,
     };
     room.notify_all(&action).await;
 
-    if room.current().hand.len() == 10 || room.current().score().is_bust() {
+    if room.current().hand.cards.len() == 10 || room.current().score().is_bust() {
         println!("{who} has dealt the max hand");
         let action = ServerAction::EndTurn;
         room.notify_current(&action).await;
     }
 }
 
-async fn split(state: &Arc<Mutex<MyState>>, id: &RoomId, who: Who, account_id: i64) {
-    //TODO: Verify
+/// Splits the current hand in two, betting as much again on the new one.
+/// Returns whether that was allowed
+async fn split(state: &Arc<Mutex<MyState>>, id: &RoomId, who: Who, account_id: i64) -> bool {
     println!("{who} has requested a split");
     let mut lock = state.lock().await;
     let room = lock.rooms.get_mut(id).unwrap();
+    if !room.is_turn_of(who) || room.current().bet == 0 || !room.current().hand.can_split() {
+        println!("{who} can't split right now");
+        return false;
+    }
+    let bet = room.current().bet;
+    if i64::from(bet) > room.balance(account_id).await {
+        println!("{who} can't afford to split");
+        return false;
+    }
+    room.grade(Decision::Split).await;
+    room.log(Move::Split, Some(bet), None);
+    let entry = Entry::new(account_id, -i64::from(bet), LedgerKind::Bet)
+        .room(id)
+        .note("Split".to_string());
+    room.apply(entry).await;
 
     let cards = room.decks.split_off(room.decks.len() - 2);
-    let mut hand = Hand::new(who, vec![], true, account_id);
-    let idx = room.find_first_hand(&hand);
-    let mv_card = room.hands[idx].hand.pop().unwrap();
-    room.hands[idx].hand.push(cards[1]);
+    let current = room.current().id();
+    let idx = room
+        .hands
+        .iter()
+        .position(|hand| hand.id() == current)
+        .unwrap();
+    let seat = room.current().seat();
+    let new_hand = room.hands[idx].hand.split();
+    let mut hand = Hand::new(room.new_hand_id(), who, seat, new_hand, account_id);
+    room.hands[idx].hand.cards.push(cards[1]);
     hand.bet = room.hands[idx].bet;
 
-    let action = ServerAction::PlayerSplit { player: idx };
+    let action = ServerAction::PlayerSplit {
+        hand: current,
+        new_hand: hand.id(),
+    };
+    room.notify_all(&action).await;
+    let action = ServerAction::BetPlaced {
+        hand: hand.id(),
+        amount: hand.bet,
+    };
     room.notify_all(&action).await;
     let action = ServerAction::Dealt {
-        hand: idx,
+        hand: hand.id(),
         card: Some(cards[0]),
-        second_hand: true,
     };
     room.notify_all(&action).await;
     let action = ServerAction::Dealt {
-        hand: idx,
+        hand: current,
         card: Some(cards[1]),
-        second_hand: false,
     };
     room.notify_all(&action).await;
 
-    hand.hand.push(mv_card);
-    hand.hand.push(cards[0]);
-    if hand.hand[0].rank == Rank::Ace {
+    hand.hand.cards.push(cards[0]);
+    if hand.hand.is_split_aces() {
         let action = ServerAction::EndTurn;
         room.notify_current(&action).await;
     }
-    room.hands.push(hand);
+    //The second hand is played straight after the first
+    room.hands.insert(idx + 1, hand);
+    true
 }
 
 async fn leave(state: &Arc<Mutex<MyState>>, id: &RoomId, who: Who) {
     println!("{who} has closed the connection");
     let mut lock = state.lock().await;
     if let Some(room) = lock.rooms.get_mut(id) {
+        if !room.sockets.contains(&who) {
+            println!("{who} had already been removed from the game");
+            return;
+        }
         if room.sockets.len() == 1 {
-            lock.rooms.remove(id).unwrap();
+            let mut room = lock.remove_room(id).unwrap();
+            room.dismiss_spectators(&ServerAction::RoomClosed).await;
             println!("The last player left the game");
             return;
         }
-        let _old_connection = room.sockets.remove(&who).unwrap();
-
-        let hands = std::mem::replace(&mut room.hands, vec![]);
-        let (old_indexes, remaining_hands): (_, Vec<_>) = hands
-            .into_iter()
-            .enumerate()
-            .partition(|(_, hand)| hand.who() == &who);
-        let remaining_hands = remaining_hands.into_iter().map(|p| p.1).collect();
-        room.hands = remaining_hands;
-        let current = room.current_hand();
-        let was_current = old_indexes
-            .iter()
-            .position(|(idx, _)| *idx == current)
-            .is_some();
+        room.remove_player(who).await;
+    } else {
+        println!("Player left non-existent game");
+    }
+}
+
+impl Room {
+    /// Takes `who` out of the game, moving play on if it was their turn. The
+    /// room must have other players left
+    async fn remove_player(&mut self, who: Who) {
+        //Bots never had a connection
+        let _old_connection = self.sockets.remove(&who);
+        self.players.remove(&who);
+        self.bots.remove(&who);
 
-        for (idx, _) in &old_indexes {
-            let action = ServerAction::PlayerLeave { player: *idx };
-            room.notify_all(&action).await;
+        let (seats, was_current) = self.remove_hands(who);
+        for seat in seats {
+            let action = ServerAction::PlayerLeave { seat };
+            self.notify_all(&action).await;
         }
+        self.publish();
 
-        if was_current {
-            if room.started {
-                if old_indexes.iter().any(|(idx, _)| *idx == room.hands.len()) {
-                    room.notify_game_end().await;
-                } else {
-                    let action = ServerAction::RequestBet;
-                    room.notify_current(&action).await;
-                }
+        if self.is_host(who) {
+            //Whoever has been seated longest takes over, as long as they aren't a bot
+            let next = *self
+                .hands
+                .iter()
+                .map(Hand::who)
+                .find(|who| !self.bots.contains_key(who))
+                .unwrap();
+            println!("{next} has taken over as host of {}", self.id);
+            self.set_host(next).await;
+        }
+
+        if was_current && self.phase() == Phase::Playing {
+            if self.current_id().is_none() {
+                self.notify_game_end().await;
             } else {
-                let action = ServerAction::NewHost;
-                room.notify_current(&action).await;
+                let action = ServerAction::RequestBet {
+                    hand: self.current().id(),
+                };
+                self.notify_current(&action).await;
             }
         }
-    } else {
-        println!("Player left non-existent game");
     }
-}
 
-impl Room {
+    /// Tells `who` which seats are theirs
+    async fn notify_seats(&mut self, who: Who) {
+        let seats = self.seats_of(who);
+        self.notify_player(who, &ServerAction::YourSeats { seats })
+            .await;
+    }
+
+    /// Makes `who` the host, telling the previous host they no longer are
+    async fn set_host(&mut self, who: Who) {
+        if let Some(old) = self.host.replace(who) {
+            if old != who {
+                self.notify_player(old, &ServerAction::NoLongerHost).await;
+            }
+        }
+        let action = ServerAction::NewHost {
+            started: self.phase() == Phase::Playing,
+        };
+        self.notify_player(who, &action).await;
+        let Some(&seat) = self.seats_of(who).first() else {
+            return;
+        };
+        let action = ServerAction::HostChanged { seat };
+        self.notify_all(&action).await;
+    }
+
+    /// Gives back every bet that hasn't been settled yet, for `who` or for everyone
+    pub async fn refund_bets(&mut self, who: Option<Who>) {
+        if self.finished {
+            return;
+        }
+        let mut refunds = vec![];
+        for hand in &mut self.hands {
+            if hand.bet == 0 || who.is_some_and(|who| *hand.who() != who) {
+                continue;
+            }
+            refunds.push(
+                Entry::new(hand.account_id(), i64::from(hand.bet), LedgerKind::Refund)
+                    .room(&self.id),
+            );
+            hand.bet = 0;
+        }
+        for refund in refunds {
+            self.apply(refund).await;
+        }
+    }
+
+    /// Sends `action` to `who` and then closes their connection. They still
+    /// have to be taken out of the room afterwards
+    pub async fn disconnect(&mut self, who: Who, action: &ServerAction) {
+        let Some(socket) = self.sockets.get_mut(&who) else {
+            return;
+        };
+        let msg = serde_json::to_string(action).unwrap();
+        //They may already be gone, in which case there's nobody to tell
+        let _ = socket.send(Message::Text(msg)).await;
+        let _ = socket.send(Message::Close(None)).await;
+    }
+
+    /// Sends `action` to every spectator and closes their connections
+    pub async fn dismiss_spectators(&mut self, action: &ServerAction) {
+        self.spectators.broadcast(action).await;
+        self.spectators.broadcast_close().await;
+    }
+
+    /// Removes `who` on behalf of staff, refunding them first
+    pub async fn kick(&mut self, who: Who) {
+        self.refund_bets(Some(who)).await;
+        let action = ServerAction::Kicked;
+        self.disconnect(who, &action).await;
+        self.remove_player(who).await;
+    }
+
     async fn dealer_turn(&mut self) {
         loop {
-            let score = self.dealer_hand_dummy().score();
             //TODO: Do I want to sleep here?
             tokio::time::sleep(Duration::from_millis(500)).await;
-            match score {
-                Score::Bust(_) | Score::Blackjack => break,
-                Score::Points(x) if x >= 17 => break,
-                Score::Points(_) => {
-                    let card = self.decks.pop().unwrap();
-                    let action = ServerAction::DealDealer { card: Some(card) };
-                    self.notify_all(&action).await;
-                    self.dealer_hand.push(card);
-                }
+            if !engine::dealer_hits(&self.dealer_hand, &self.rules) {
+                break;
             }
+            let card = self.decks.pop().unwrap();
+            let action = ServerAction::DealDealer { card: Some(card) };
+            self.notify_all(&action).await;
+            self.dealer_hand.push(card);
         }
     }
 
     async fn notify_game_end(&mut self) {
         //TODO: Find a better place than this
         self.dealer_turn().await;
-        let winning_players = self.calculate_winners();
-        for (hand, &result) in self.hands.iter().zip(winning_players.iter()) {
-            let amount = i64::try_from(hand.bet).unwrap();
-            let diff: i64 = match result {
-                GameResult::Lose => 0,
-                GameResult::Win => amount * 2,
-                GameResult::Push => amount,
-                GameResult::Blackjack => (2 * amount) + (amount / 2),
-            };
-            let database = self.database();
-            let database = database.lock().await;
-            let id = hand.account_id();
-            sqlx::query!(
-                "UPDATE Users
-                SET balance = balance + ?
-                WHERE id = ?",
-                diff,
-                id,
-            )
-            .execute(&*database)
-            .await
-            .unwrap();
-            let who = hand.who();
-            let socket = self.sockets.get_mut(who).unwrap();
+        let scores = self.hands.iter().map(Hand::score).collect::<Vec<_>>();
+        let winning_players = engine::results(&scores, engine::Score::of(&self.dealer_hand));
+        let records = self
+            .hands
+            .iter()
+            .zip(&winning_players)
+            .map(|(hand, &result)| HandRecord {
+                hand: hand.id(),
+                seat: hand.seat(),
+                user_id: hand.account_id(),
+                second_hand: hand.is_second(),
+                cards: hand.hand.cards.clone(),
+                bet: hand.bet,
+                result,
+                payout: result.payout(hand.bet),
+            })
+            .collect::<Vec<_>>();
+        for (idx, &result) in winning_players.iter().enumerate() {
+            let hand = &self.hands[idx];
+            let diff = result.payout(hand.bet);
             let message = ServerAction::EndGame {
+                hand: hand.id(),
                 result,
                 dealer_hand: self.dealer_hand.clone(),
             };
+            let who = *hand.who();
+            if diff > 0 {
+                let entry = Entry::new(hand.account_id(), diff, LedgerKind::Payout)
+                    .room(&self.id)
+                    .note(format!("{result:?}"));
+                self.apply(entry).await;
+            }
+            let Some(socket) = self.sockets.get_mut(&who) else {
+                continue;
+            };
             let message = serde_json::to_string(&message).unwrap();
             socket.send(Message::Text(message)).await.unwrap();
         }
-    }
-
-    fn calculate_winners(&mut self) -> Vec<GameResult> {
-        let dealer = self.dealer_hand_dummy().score();
-        if let Score::Bust(p) = dealer {
-            if self
-                .hands
-                .iter()
-                .map(|player| player.score())
-                .all(|s| s.is_bust())
-            {
-                return self
-                    .hands
-                    .iter()
-                    .map(|player| player.score())
-                    .map(|score| match score {
-                        Score::Bust(c) if c == p => GameResult::Push,
-                        Score::Bust(c) if c > p => GameResult::Win,
-                        Score::Bust(c) if c < p => GameResult::Lose,
-                        _ => unreachable!(),
-                    })
-                    .collect();
+        //Every bet has now been paid out
+        self.finished = true;
+        self.hands.iter_mut().for_each(|hand| hand.bet = 0);
+        let running = self.count().running;
+        if let Some(training) = &mut self.training {
+            let ask = training.maybe_ask_count(running);
+            let action = ServerAction::TrainingReport {
+                stats: training.stats(),
+                over: false,
+            };
+            self.notify_all(&action).await;
+            if ask {
+                self.notify_all(&ServerAction::CountQuiz).await;
             }
-        };
-        //TODO: If the second card dealt to a split ace is a 10-point card you do not
-        // receive the blackjack bonus for this hand. It does however win against an
-        // ordinary 21 made of more than two cards. If the dealer also has a blackjack
-        // the result for this hand is a push as usual.
-        self.hands
-            .iter()
-            .map(|player| player.score())
-            .map(|score| {
-                if score.is_bust() {
-                    return GameResult::Lose;
-                }
-                match dealer {
-                    Score::Blackjack => {
-                        if score.is_blackjack() {
-                            GameResult::Push
-                        } else {
-                            GameResult::Lose
-                        }
-                    }
-                    Score::Bust(_) => {
-                        if score.is_blackjack() {
-                            GameResult::Blackjack
-                        } else {
-                            GameResult::Win
-                        }
-                    }
-                    Score::Points(points) => match score {
-                        Score::Blackjack => GameResult::Blackjack,
-                        Score::Points(p) if p > points => GameResult::Win,
-                        Score::Points(p) if p < points => GameResult::Lose,
-                        Score::Bust(_) => unreachable!(),
-                        _ => GameResult::Push,
-                    },
-                }
-            })
-            .collect::<Vec<_>>()
+        }
+        //Spectators don't get a result of their own, so show them the final table
+        let action = self.table_state();
+        self.spectators.broadcast(&action).await;
+        self.publish();
+        if let Some(mut round) = self.round.take() {
+            round.replay(&self.replay_state());
+            let database = self.database();
+            let database = database.lock().await;
+            let training = self.training.is_some();
+            let round = round
+                .save(
+                    &database,
+                    &self.id,
+                    &self.rules,
+                    training,
+                    &self.dealer_hand,
+                    records,
+                )
+                .await;
+            println!("Saved round {round} of {}", self.id);
+        }
     }
 }
 
-#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
-pub enum GameResult {
-    Lose,
-    Win,
-    Push,
-    Blackjack,
-}
-
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 pub enum PlayerAction {
     GameStart,
     Deal,
     EndTurn,
     Split,
-    Bet { amount: u32 },
+    Bet {
+        amount: u32,
+    },
+    RevokeInvites,
+    /// Host only: removes whoever sits in `seat`, refunding their bets
+    Kick {
+        seat: usize,
+    },
+    /// Host only: hands the table over to whoever sits in `seat`
+    TransferHost {
+        seat: usize,
+    },
+    /// Host only: stops or allows new players sitting down
+    LockTable {
+        locked: bool,
+    },
+    /// Plays another seat as well, if the table allows it
+    TakeSeat {
+        seat: usize,
+    },
+    ChangeSeat {
+        from: usize,
+        to: usize,
+    },
+    LeaveSeat {
+        seat: usize,
+    },
+    /// Asks what basic strategy would do with the current hand
+    Hint,
+    /// Host only: seats a bot in `seat`. Bots are removed by kicking them
+    AddBot {
+        seat: usize,
+        strategy: BotKind,
+    },
+    Chat {
+        text: String,
+    },
+    /// Flags a message for moderators, by its id
+    ReportChat {
+        id: u64,
+    },
+    /// Moderators only: stops whoever sits in `seat` chatting for `minutes`
+    MuteChat {
+        seat: usize,
+        minutes: u32,
+    },
+    /// Training only: what the player thinks the running count is
+    CountAnswer {
+        count: i32,
+    },
+    /// Training only: finishes the session
+    EndTraining,
 }
 
+/// Players are referred to by the seat they sit in and hands by their
+/// `HandId`, neither of which change when someone else leaves
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub enum ServerAction {
     PlayerJoin {
-        player: usize,
+        seat: usize,
+        hand: HandId,
+        player: PlayerInfo,
     },
     PlayerLeave {
-        player: usize,
+        seat: usize,
+    },
+    /// The seats that belong to you
+    YourSeats {
+        seats: Vec<usize>,
+    },
+    /// You are now the host. A round may already be under way
+    NewHost {
+        started: bool,
+    },
+    NoLongerHost,
+    HostChanged {
+        seat: usize,
+    },
+    TableLocked {
+        locked: bool,
     },
-    NewHost,
+    /// The table has been cleared for another round
+    NewRound,
     Dealt {
-        hand: usize,
+        hand: HandId,
         card: Option<Card>,
-        second_hand: bool,
     },
+    /// `hand` was split, with its second card moving to `new_hand`
     PlayerSplit {
-        player: usize,
+        hand: HandId,
+        new_hand: HandId,
+    },
+    RequestBet {
+        hand: HandId,
+    },
+    BetPlaced {
+        hand: HandId,
+        amount: u32,
     },
-    RequestBet,
     YourTurn {
+        hand: HandId,
         can_split: bool,
     },
     EndTurn,
     EndGame {
+        hand: HandId,
         result: GameResult,
         dealer_hand: Vec<Card>,
     },
     DealDealer {
         card: Option<Card>,
     },
+    /// Staff removed you from the room
+    Kicked,
+    /// The room was closed, refunding any bets
+    RoomClosed,
+    /// Sent to the owner of a private room after revoking old invites
+    InviteLink {
+        link: String,
+    },
+    /// A snapshot of the table, sent to spectators as they arrive
+    TableState {
+        phase: Phase,
+        hands: Vec<SeenHand>,
+        dealer: Vec<Option<Card>>,
+        current: Option<HandId>,
+        host: Option<usize>,
+        locked: bool,
+        spectators: usize,
+        chat: Vec<ChatMessage>,
+    },
+    Spectators {
+        count: usize,
+    },
+    Chat {
+        id: u64,
+        from: PlayerInfo,
+        text: String,
+        at: i64,
+    },
+    /// Why the last message someone sent wasn't posted
+    ChatRejected {
+        reason: String,
+    },
+    ChatReported {
+        id: u64,
+    },
+    Hint {
+        advice: Advice,
+        reason: String,
+    },
+    /// How a training player's decision compares with basic strategy
+    Graded {
+        correct: bool,
+        advice: Advice,
+        reason: String,
+    },
+    /// Asks a training player for the running count
+    CountQuiz,
+    CountChecked {
+        correct: bool,
+        count: i32,
+    },
+    /// How a training session is going, or how it went once it's `over`
+    TrainingReport {
+        stats: TrainingStats,
+        over: bool,
+    },
 }
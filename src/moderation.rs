@@ -0,0 +1,144 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    response::{Html, IntoResponse, Redirect},
+    routing::{get, post},
+    Extension, Form, Router,
+};
+use axum_login::{
+    axum_sessions::extractors::{ReadableSession, WritableSession},
+    RequireAuthorizationLayer,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use tokio::sync::Mutex;
+
+use crate::{
+    chat, csrf,
+    data::unix_time,
+    routes::{error_403, error_404, TERA},
+    AppState, Role, User,
+};
+
+/// Routes under `/moderation`, for moderators and admins
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(reports))
+        .route("/reports/:id/mute", post(mute))
+        .route("/reports/:id/dismiss", post(dismiss))
+        .route_layer(
+            RequireAuthorizationLayer::<i64, User, Role>::login_with_role(Role::Moderator..),
+        )
+}
+
+#[derive(Serialize)]
+struct Report {
+    id: i64,
+    room: String,
+    reporter: String,
+    reported: String,
+    text: String,
+    sent_at: i64,
+    muted_until: Option<i64>,
+}
+
+async fn reports(
+    State((_state, database)): State<AppState>,
+    mut session: WritableSession,
+) -> impl IntoResponse {
+    let conn = database.lock().await;
+    let reports = sqlx::query_as!(
+        Report,
+        r#"SELECT ChatReports.id AS "id!", ChatReports.room, Reporters.username AS reporter,
+            Reported.username AS reported, ChatReports.text, ChatReports.sent_at,
+            Reported.muted_until
+        FROM ChatReports
+        JOIN Users AS Reporters ON Reporters.id = ChatReports.reporter_id
+        JOIN Users AS Reported ON Reported.id = ChatReports.reported_id
+        WHERE ChatReports.resolved_by IS NULL
+        ORDER BY ChatReports.id
+        LIMIT 100"#
+    )
+    .fetch_all(&*conn)
+    .await
+    .unwrap();
+    drop(conn);
+    let mut context = tera::Context::new();
+    context.insert("reports", &reports);
+    context.insert("now", &unix_time());
+    context.insert("csrf_token", &csrf::token(&mut session));
+    Html(TERA.render("moderation.html", &context).unwrap())
+}
+
+#[derive(Deserialize)]
+struct MuteForm {
+    minutes: u32,
+    csrf_token: String,
+}
+
+/// Mutes whoever sent the reported message, which settles the report
+async fn mute(
+    Path(id): Path<i64>,
+    ConnectInfo(who): ConnectInfo<SocketAddr>,
+    State((_state, database)): State<AppState>,
+    Extension(moderator): Extension<User>,
+    session: ReadableSession,
+    Form(form): Form<MuteForm>,
+) -> impl IntoResponse {
+    if !csrf::verify(&session, &form.csrf_token) {
+        return error_403().into_response();
+    }
+    let conn = database.lock().await;
+    let reported = sqlx::query_scalar!(
+        "SELECT reported_id FROM ChatReports WHERE id = ? AND resolved_by IS NULL",
+        id
+    )
+    .fetch_optional(&*conn)
+    .await
+    .unwrap();
+    drop(conn);
+    let Some(reported) = reported else {
+        return error_404().await.into_response();
+    };
+    chat::mute(&database, reported, form.minutes).await;
+    resolve(&database, id, moderator.id).await;
+    println!(
+        "{} ({who}) muted user {reported} for {} minutes over report {id}",
+        moderator.username, form.minutes
+    );
+    Redirect::to("/moderation").into_response()
+}
+
+#[derive(Deserialize)]
+struct CsrfForm {
+    csrf_token: String,
+}
+
+async fn dismiss(
+    Path(id): Path<i64>,
+    ConnectInfo(who): ConnectInfo<SocketAddr>,
+    State((_state, database)): State<AppState>,
+    Extension(moderator): Extension<User>,
+    session: ReadableSession,
+    Form(form): Form<CsrfForm>,
+) -> impl IntoResponse {
+    if !csrf::verify(&session, &form.csrf_token) {
+        return error_403().into_response();
+    }
+    println!("{} ({who}) dismissed report {id}", moderator.username);
+    resolve(&database, id, moderator.id).await;
+    Redirect::to("/moderation").into_response()
+}
+
+async fn resolve(database: &Arc<Mutex<SqlitePool>>, id: i64, moderator: i64) {
+    let conn = database.lock().await;
+    sqlx::query!(
+        "UPDATE ChatReports SET resolved_by = ? WHERE id = ? AND resolved_by IS NULL",
+        moderator,
+        id
+    )
+    .execute(&*conn)
+    .await
+    .unwrap();
+}
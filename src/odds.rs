@@ -0,0 +1,334 @@
+//! Exact odds for a round, worked out card by card from what's left in the
+//! shoe rather than by simulation
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::{
+    card::Card,
+    engine::{Score, TableRules},
+};
+
+/// How many cards of each value haven't been seen yet, aces first and every
+/// ten-point card counted together
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Shoe {
+    counts: [u16; 10],
+}
+
+impl Shoe {
+    pub fn new(cards: &[Card]) -> Self {
+        let mut shoe = Self { counts: [0; 10] };
+        for &card in cards {
+            shoe.add(card);
+        }
+        shoe
+    }
+
+    pub fn add(&mut self, card: Card) {
+        self.counts[usize::from(card.score_card()) - 1] += 1;
+    }
+
+    pub fn len(&self) -> u32 {
+        self.counts.iter().map(|&count| u32::from(count)).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every card value that could come next, how likely it is, and what's
+    /// left once it has
+    fn draws(self) -> impl Iterator<Item = (u8, f64, Shoe)> {
+        let len = f64::from(self.len());
+        (0..10).filter(move |&i| self.counts[i] > 0).map(move |i| {
+            let mut rest = self;
+            rest.counts[i] -= 1;
+            (i as u8 + 1, f64::from(self.counts[i]) / len, rest)
+        })
+    }
+}
+
+/// How likely the dealer is to finish on each total
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct DealerOdds {
+    /// Standing on 17 through 21, not counting blackjack
+    pub totals: [f64; 5],
+    pub blackjack: f64,
+    pub bust: f64,
+    /// Busting on 22 through 26, which matters when every hand busts too
+    pub bust_totals: [f64; 5],
+}
+
+impl DealerOdds {
+    fn add(&mut self, other: &DealerOdds, weight: f64) {
+        for (total, other) in self.totals.iter_mut().zip(other.totals) {
+            *total += other * weight;
+        }
+        for (total, other) in self.bust_totals.iter_mut().zip(other.bust_totals) {
+            *total += other * weight;
+        }
+        self.blackjack += other.blackjack * weight;
+        self.bust += other.bust * weight;
+    }
+}
+
+/// What a hand is expected to win or lose per unit bet, for each play
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PlayerEv {
+    pub stand: f64,
+    /// Taking a card, then hitting or standing, whichever is better
+    pub hit: f64,
+    /// Taking exactly one card at twice the bet
+    pub double: f64,
+}
+
+/// The total counting a usable ace as 11, and whether it is
+fn soft_total(hard: u8, ace: bool) -> (u8, bool) {
+    if ace && hard + 10 <= 21 {
+        (hard + 10, true)
+    } else {
+        (hard, false)
+    }
+}
+
+fn signum(a: u8, b: u8) -> f64 {
+    match a.cmp(&b) {
+        std::cmp::Ordering::Less => -1.0,
+        std::cmp::Ordering::Equal => 0.0,
+        std::cmp::Ordering::Greater => 1.0,
+    }
+}
+
+/// The dealer's hand so far, and the rule they play to
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct DealerKey {
+    shoe: Shoe,
+    hard: u8,
+    ace: bool,
+    /// How many cards, up to three, since only two can make a blackjack
+    cards: u8,
+    hits_soft_17: bool,
+}
+
+/// A player's hand of three or more cards, against an upcard
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct PlayerKey {
+    shoe: Shoe,
+    hard: u8,
+    ace: bool,
+    upcard: u8,
+    hits_soft_17: bool,
+}
+
+/// Works out odds, remembering every position it has seen so asking about
+/// several hands from the same shoe stays cheap
+#[derive(Default)]
+pub struct Calculator {
+    dealer: HashMap<DealerKey, DealerOdds>,
+    player: HashMap<PlayerKey, f64>,
+}
+
+impl Calculator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The dealer's chances of finishing on each total with `upcard` showing,
+    /// when `shoe` holds every card not yet seen, the hole card included
+    pub fn dealer(&mut self, shoe: &Shoe, upcard: Card, rules: &TableRules) -> DealerOdds {
+        let value = upcard.score_card();
+        self.dealer_from(DealerKey {
+            shoe: *shoe,
+            hard: value,
+            ace: value == 1,
+            cards: 1,
+            hits_soft_17: rules.dealer_hits_soft_17,
+        })
+    }
+
+    fn dealer_from(&mut self, key: DealerKey) -> DealerOdds {
+        if let Some(odds) = self.dealer.get(&key) {
+            return *odds;
+        }
+        let (total, soft) = soft_total(key.hard, key.ace);
+        let mut odds = DealerOdds::default();
+        if key.hard > 21 {
+            odds.bust = 1.0;
+            odds.bust_totals[usize::from(key.hard - 22)] = 1.0;
+        } else if key.cards == 2 && total == 21 {
+            odds.blackjack = 1.0;
+        } else if key.cards >= 2 && (total > 17 || (total == 17 && !(soft && key.hits_soft_17))) {
+            odds.totals[usize::from(total - 17)] = 1.0;
+        } else {
+            for (value, chance, shoe) in key.shoe.draws() {
+                let next = self.dealer_from(DealerKey {
+                    shoe,
+                    hard: key.hard + value,
+                    ace: key.ace || value == 1,
+                    cards: (key.cards + 1).min(3),
+                    ..key
+                });
+                odds.add(&next, chance);
+            }
+        }
+        self.dealer.insert(key, odds);
+        odds
+    }
+
+    /// What standing, hitting and doubling are each worth for `cards`, when
+    /// `shoe` holds every card not yet seen. Treats the hand as the only one
+    /// at the table, so a bust is compared with the dealer's if they bust too
+    pub fn player(
+        &mut self,
+        shoe: &Shoe,
+        cards: &[Card],
+        upcard: Card,
+        rules: &TableRules,
+    ) -> PlayerEv {
+        let hard = cards.iter().map(Card::score_card).sum();
+        let ace = cards.iter().any(|card| card.score_card() == 1);
+        let stand = if Score::of(cards).is_blackjack() {
+            let dealer = self.dealer(shoe, upcard, rules);
+            1.5 * (1.0 - dealer.blackjack)
+        } else {
+            self.stand(shoe, hard, ace, upcard, rules)
+        };
+        let mut hit = 0.0;
+        let mut double = 0.0;
+        for (value, chance, rest) in shoe.draws() {
+            let (hard, ace) = (hard + value, ace || value == 1);
+            hit += chance * self.best(&rest, hard, ace, upcard, rules);
+            double += chance * 2.0 * self.stand(&rest, hard, ace, upcard, rules);
+        }
+        PlayerEv { stand, hit, double }
+    }
+
+    /// Standing on a hand that isn't a blackjack
+    fn stand(&mut self, shoe: &Shoe, hard: u8, ace: bool, upcard: Card, rules: &TableRules) -> f64 {
+        let dealer = self.dealer(shoe, upcard, rules);
+        if hard > 21 {
+            let both_bust = (22..)
+                .zip(dealer.bust_totals)
+                .map(|(total, chance)| chance * signum(hard, total))
+                .sum::<f64>();
+            return both_bust - (1.0 - dealer.bust);
+        }
+        let (total, _) = soft_total(hard, ace);
+        let against_totals = (17..)
+            .zip(dealer.totals)
+            .map(|(dealer_total, chance)| chance * signum(total, dealer_total))
+            .sum::<f64>();
+        against_totals + dealer.bust - dealer.blackjack
+    }
+
+    /// The most a hand of three or more cards is worth, hitting or standing
+    fn best(&mut self, shoe: &Shoe, hard: u8, ace: bool, upcard: Card, rules: &TableRules) -> f64 {
+        let stand = self.stand(shoe, hard, ace, upcard, rules);
+        if hard > 21 {
+            return stand;
+        }
+        let key = PlayerKey {
+            shoe: *shoe,
+            hard,
+            ace,
+            upcard: upcard.score_card(),
+            hits_soft_17: rules.dealer_hits_soft_17,
+        };
+        let hit = match self.player.get(&key) {
+            Some(&hit) => hit,
+            None => {
+                let mut hit = 0.0;
+                for (value, chance, rest) in shoe.draws() {
+                    hit +=
+                        chance * self.best(&rest, hard + value, ace || value == 1, upcard, rules);
+                }
+                self.player.insert(key, hit);
+                hit
+            }
+        };
+        stand.max(hit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::{Rank, Suit};
+
+    fn card(rank: Rank) -> Card {
+        Card {
+            suit: Suit::Clubs,
+            rank,
+        }
+    }
+
+    /// A fresh shoe with `seen` already dealt from it
+    fn shoe(decks: u8, seen: &[Card]) -> Shoe {
+        let mut shoe = Shoe::new(&Card::one_deck().repeat(decks.into()));
+        for card in seen {
+            shoe.counts[usize::from(card.score_card()) - 1] -= 1;
+        }
+        shoe
+    }
+
+    fn total(odds: &DealerOdds) -> f64 {
+        odds.totals.iter().sum::<f64>() + odds.blackjack + odds.bust
+    }
+
+    #[test]
+    fn dealer_busts_a_6_about_42_percent() {
+        let six = card(Rank::Six);
+        let odds = Calculator::new().dealer(&shoe(6, &[six]), six, &TableRules::default());
+        assert!((0.418..0.428).contains(&odds.bust), "{}", odds.bust);
+        assert_eq!(odds.blackjack, 0.0);
+    }
+
+    #[test]
+    fn dealer_outcomes_add_up() {
+        let mut calculator = Calculator::new();
+        for rules in [
+            TableRules::default(),
+            TableRules {
+                dealer_hits_soft_17: true,
+                ..TableRules::default()
+            },
+        ] {
+            for rank in [Rank::Ace, Rank::Two, Rank::Seven, Rank::King] {
+                let up = card(rank);
+                let odds = calculator.dealer(&shoe(2, &[up]), up, &rules);
+                assert!((total(&odds) - 1.0).abs() < 1e-9, "{rank:?}");
+                let bust_totals = odds.bust_totals.iter().sum::<f64>();
+                assert!((bust_totals - odds.bust).abs() < 1e-9, "{rank:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn ace_makes_blackjack_with_any_ten() {
+        let ace = card(Rank::Ace);
+        let odds = Calculator::new().dealer(&shoe(6, &[ace]), ace, &TableRules::default());
+        let tens = 6.0 * 16.0 / (6.0 * 52.0 - 1.0);
+        assert!((odds.blackjack - tens).abs() < 1e-9);
+    }
+
+    #[test]
+    fn standing_on_20_against_a_10_wins_on_average() {
+        let hand = [card(Rank::King), card(Rank::Queen)];
+        let up = card(Rank::Ten);
+        let seen = [hand[0], hand[1], up];
+        let ev = Calculator::new().player(&shoe(6, &seen), &hand, up, &TableRules::default());
+        assert!(ev.stand > 0.0, "{}", ev.stand);
+        assert!(ev.stand > ev.hit && ev.stand > ev.double);
+    }
+
+    #[test]
+    fn blackjack_pays_unless_the_dealer_has_one_too() {
+        let hand = [card(Rank::Ace), card(Rank::King)];
+        let up = card(Rank::Five);
+        let seen = [hand[0], hand[1], up];
+        let ev = Calculator::new().player(&shoe(6, &seen), &hand, up, &TableRules::default());
+        assert_eq!(ev.stand, 1.5);
+    }
+}
@@ -0,0 +1,197 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+    Extension, Form, Json,
+};
+use axum_login::axum_sessions::extractors::{ReadableSession, WritableSession};
+use enum_iterator::{all, Sequence};
+use nutype::nutype;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    csrf,
+    routes::{error_403, TERA},
+    stats, AppState, User,
+};
+
+/// The picture shown next to someone's name at the table
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Sequence, sqlx::Type,
+)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum Avatar {
+    #[default]
+    Spade,
+    Heart,
+    Diamond,
+    Club,
+    Star,
+    Crown,
+    Dice,
+    Chip,
+}
+
+#[nutype(
+    sanitize(trim)
+    validate(
+        min_len = 1,
+        max_len = 24,
+        with = |s: &str| !s.chars().any(char::is_control)
+    )
+)]
+#[derive(Deserialize, Serialize, *)]
+pub struct DisplayName(String);
+
+/// What everyone at a table can see about a player
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlayerInfo {
+    pub username: String,
+    pub display_name: String,
+    pub avatar: Avatar,
+}
+
+impl From<&User> for PlayerInfo {
+    fn from(user: &User) -> Self {
+        Self {
+            username: user.username.clone(),
+            display_name: user
+                .display_name
+                .clone()
+                .unwrap_or_else(|| user.username.clone()),
+            avatar: user.avatar,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Saved {
+    #[serde(default)]
+    saved: bool,
+}
+
+fn settings_page(
+    user: &User,
+    csrf_token: &str,
+    display_name: &str,
+    avatar: Avatar,
+    on_leaderboards: bool,
+    saved: bool,
+    error: Option<&str>,
+) -> Html<String> {
+    let mut context = tera::Context::new();
+    context.insert("username", &user.username);
+    context.insert("display_name", display_name);
+    context.insert("avatar", &avatar);
+    context.insert("avatars", &all::<Avatar>().collect::<Vec<_>>());
+    context.insert("on_leaderboards", &on_leaderboards);
+    context.insert("saved", &saved);
+    context.insert("error", &error);
+    context.insert("csrf_token", csrf_token);
+    Html(TERA.render("settings.html", &context).unwrap())
+}
+
+pub async fn settings(
+    Query(query): Query<Saved>,
+    Extension(user): Extension<User>,
+    mut session: WritableSession,
+) -> impl IntoResponse {
+    let csrf_token = csrf::token(&mut session);
+    let display_name = user.display_name.clone().unwrap_or_default();
+    settings_page(
+        &user,
+        &csrf_token,
+        &display_name,
+        user.avatar,
+        user.on_leaderboards,
+        query.saved,
+        None,
+    )
+}
+
+#[derive(Deserialize)]
+pub struct SettingsForm {
+    display_name: String,
+    avatar: Avatar,
+    /// Unticked checkboxes aren't sent at all
+    #[serde(default)]
+    on_leaderboards: bool,
+    csrf_token: String,
+}
+
+pub async fn recieve_settings(
+    ConnectInfo(who): ConnectInfo<SocketAddr>,
+    State((_state, database)): State<AppState>,
+    Extension(user): Extension<User>,
+    session: ReadableSession,
+    Form(form): Form<SettingsForm>,
+) -> Response {
+    if !csrf::verify(&session, &form.csrf_token) {
+        println!(
+            "{} ({who}) tried to change their settings without a valid CSRF token",
+            user.username
+        );
+        return error_403().into_response();
+    }
+    //Leaving the display name empty goes back to showing the username
+    let display_name = if form.display_name.trim().is_empty() {
+        None
+    } else {
+        match DisplayName::new(&form.display_name) {
+            Ok(name) => Some(name.into_inner()),
+            Err(_) => {
+                let error =
+                    "Display names can be at most 24 characters, with no control characters";
+                let page = settings_page(
+                    &user,
+                    &form.csrf_token,
+                    &form.display_name,
+                    form.avatar,
+                    form.on_leaderboards,
+                    false,
+                    Some(error),
+                );
+                return (StatusCode::BAD_REQUEST, page).into_response();
+            }
+        }
+    };
+    let conn = database.lock().await;
+    sqlx::query!(
+        "UPDATE Users SET display_name = ?, avatar = ?, on_leaderboards = ? WHERE id = ?",
+        display_name,
+        form.avatar,
+        form.on_leaderboards,
+        user.id
+    )
+    .execute(&*conn)
+    .await
+    .unwrap();
+    println!("{} ({who}) updated their settings", user.username);
+    Redirect::to("/settings?saved=true").into_response()
+}
+
+pub async fn profile(
+    State((_state, database)): State<AppState>,
+    Extension(user): Extension<User>,
+) -> impl IntoResponse {
+    let conn = database.lock().await;
+    let stats = stats::of(&conn, user.id).await;
+    drop(conn);
+    let mut context = tera::Context::new();
+    context.insert("player", &PlayerInfo::from(&user));
+    context.insert("balance", &user.balance);
+    context.insert("stats", &stats);
+    Html(TERA.render("profile.html", &context).unwrap())
+}
+
+/// Points to chart the balance from
+pub async fn balance_history(
+    State((_state, database)): State<AppState>,
+    Extension(user): Extension<User>,
+) -> impl IntoResponse {
+    let conn = database.lock().await;
+    Json(stats::balance_history(&conn, user.id).await)
+}
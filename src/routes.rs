@@ -1,24 +1,29 @@
-use std::{net::SocketAddr, sync::Arc};
+use std::net::SocketAddr;
 
 use axum::{
     extract::{ConnectInfo, Path, Query, State, WebSocketUpgrade},
-    http::StatusCode,
-    response::{Html, IntoResponse, Redirect},
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
     Extension, Form,
 };
+use axum_login::axum_sessions::extractors::{ReadableSession, WritableSession};
 use once_cell::sync::Lazy;
 use serde::Deserialize;
-use sqlx::SqlitePool;
 use tera::Tera;
-use tokio::sync::Mutex;
 
 use crate::{
-    card::Card,
-    data::{new_id, MyState, Room, RoomId},
-    websocket, Auth, User,
+    access::{Access, Visibility},
+    auth::{self, AttemptKey, Username, Verified},
+    chat, csrf,
+    data::{new_id, Room, RoomId},
+    economy::{self, RebuyRefused},
+    engine::TableRules,
+    profile::Avatar,
+    training::{self, Training},
+    websocket, AppState, Auth, Joining, Role, User,
 };
 
-static TERA: Lazy<Tera> = Lazy::new(|| match Tera::new("templates/**/*") {
+pub static TERA: Lazy<Tera> = Lazy::new(|| match Tera::new("templates/**/*") {
     Ok(t) => t,
     Err(e) => {
         eprintln!("Error parsing: {e}");
@@ -30,25 +35,73 @@ pub fn template_force() {
     Lazy::force(&TERA);
 }
 
-pub async fn home(Extension(user): Extension<User>) -> impl IntoResponse {
+#[derive(Deserialize)]
+pub struct Welcome {
+    /// Set when logging in just paid out the daily bonus
+    #[serde(default)]
+    bonus: bool,
+}
+
+pub async fn home(
+    Query(welcome): Query<Welcome>,
+    State((state, database)): State<AppState>,
+    Extension(user): Extension<User>,
+    mut session: WritableSession,
+) -> impl IntoResponse {
     let mut context = tera::Context::new();
+    if welcome.bonus {
+        context.insert("bonus", &economy::ECONOMY.daily_bonus);
+    }
+    let betting = state.lock().await.has_live_bet(user.id);
+    let conn = database.lock().await;
+    match economy::can_rebuy(&conn, user.id, user.balance, betting).await {
+        Ok(()) => context.insert("rebuy_to", &economy::ECONOMY.rebuy_to),
+        Err(RebuyRefused::TooSoon(remaining)) => {
+            context.insert("rebuy_wait", &((remaining + 59) / 60))
+        }
+        Err(RebuyRefused::BetInPlay) => context.insert("rebuy_betting", &true),
+        Err(RebuyRefused::NotBroke) => {}
+    }
+    drop(conn);
     context.insert("balance", &user.balance);
+    context.insert("play_money", &training::PLAY_MONEY);
+    context.insert("is_admin", &(user.role == Role::Admin));
+    context.insert("is_moderator", &(user.role >= Role::Moderator));
+    context.insert("csrf_token", &csrf::token(&mut session));
     Html(TERA.render("index.html", &context).unwrap())
 }
 
 #[derive(Deserialize)]
 pub struct Failed {
+    #[serde(default)]
     failed: bool,
+    #[serde(default)]
+    locked: bool,
+    #[serde(default)]
+    banned: bool,
 }
 
-pub async fn login(failed: Option<Query<Failed>>, auth: Auth) -> impl IntoResponse {
+pub async fn login(
+    failed: Option<Query<Failed>>,
+    auth: Auth,
+    mut session: WritableSession,
+) -> impl IntoResponse {
     if auth.current_user.is_some() {
         return Redirect::to("/").into_response();
     }
     let mut context = tera::Context::new();
-    if let Some(Query(Failed { failed: true })) = failed {
-        println!("Failed login");
-        context.insert("failed", &true);
+    context.insert("csrf_token", &csrf::token(&mut session));
+    if let Some(Query(failed)) = failed {
+        if failed.failed {
+            println!("Failed login");
+            context.insert("failed", &true);
+        }
+        if failed.locked {
+            context.insert("locked", &true);
+        }
+        if failed.banned {
+            context.insert("banned", &true);
+        }
     }
     Html(TERA.render("login.html", &context).unwrap()).into_response()
 }
@@ -57,130 +110,569 @@ pub async fn login(failed: Option<Query<Failed>>, auth: Auth) -> impl IntoRespon
 pub struct LoginRequest {
     username: String,
     password: String,
+    csrf_token: String,
 }
 
 pub async fn recieve_login(
     mut auth: Auth,
     ConnectInfo(who): ConnectInfo<SocketAddr>,
-    State((_state, database)): State<(Arc<Mutex<MyState>>, Arc<Mutex<SqlitePool>>)>,
+    State((_state, database)): State<AppState>,
+    session: ReadableSession,
     Form(request): Form<LoginRequest>,
 ) -> impl IntoResponse {
+    if !csrf::verify(&session, &request.csrf_token) {
+        println!("{who} tried to log in without a valid CSRF token");
+        return error_403().into_response();
+    }
+    //Logging in writes to the session
+    drop(session);
     println!("{who} is trying to log in as {}", request.username);
+    let by_name = AttemptKey::Username(&request.username);
+    let by_ip = AttemptKey::Ip(who.ip());
     let conn = database.lock().await;
+    for key in [&by_name, &by_ip] {
+        if let Some(remaining) = auth::lockout_remaining(&conn, key).await {
+            println!("{who} is locked out of logging in for another {remaining}s");
+            return Redirect::to("/login?locked=true").into_response();
+        }
+    }
     let user = sqlx::query_as!(
         User,
-        "SELECT * FROM Users 
-        WHERE username = ? AND password = ?",
+        r#"SELECT id AS "id!", username, password, balance, role AS "role: Role", banned,
+            display_name, avatar AS "avatar: Avatar", on_leaderboards
+        FROM Users
+        WHERE username = ? AND NOT bot"#,
         request.username,
-        request.password
     )
     .fetch_optional(&*conn)
     .await
     .unwrap();
-    if let Some(user) = user {
-        auth.login(&user).await.unwrap();
-        println!("{} ({who}) logged in successfully", request.username);
-        Redirect::to("/")
-    } else {
+    drop(conn);
+
+    let verified = match &user {
+        Some(user) => {
+            let stored = user.password.clone();
+            let password = request.password.clone();
+            tokio::task::spawn_blocking(move || auth::verify_password(&stored, &password))
+                .await
+                .unwrap()
+        }
+        None => Verified::Invalid,
+    };
+    let conn = database.lock().await;
+    let (Some(mut user), Verified::Valid | Verified::NeedsUpgrade) = (user, verified) else {
         println!("{who} failed to log in. Incorrect username or password");
-        Redirect::to("/login?failed=true")
+        auth::record_failure(&conn, &by_name).await;
+        auth::record_failure(&conn, &by_ip).await;
+        return Redirect::to("/login?failed=true").into_response();
+    };
+    //The address isn't cleared, so that one working account can't be used to
+    //reset the count while guessing at others
+    auth::clear_failures(&conn, &by_name).await;
+    if user.banned {
+        println!("{who} tried to log in as the banned user {}", user.username);
+        return Redirect::to("/login?banned=true").into_response();
+    }
+    let bonus = economy::claim_daily_bonus(&conn, user.id).await;
+    drop(conn);
+
+    if verified == Verified::NeedsUpgrade {
+        println!("Upgrading the stored password of {}", user.username);
+        let password = request.password;
+        //Hashing is slow, so nobody else waits on the database for it
+        let hash = tokio::task::spawn_blocking(move || auth::hash_password(&password))
+            .await
+            .unwrap();
+        let conn = database.lock().await;
+        sqlx::query!(
+            "UPDATE Users
+            SET password = ?
+            WHERE id = ?",
+            hash,
+            user.id
+        )
+        .execute(&*conn)
+        .await
+        .unwrap();
+        user.password = hash;
+    }
+
+    auth.login(&user).await.unwrap();
+    println!("{} ({who}) logged in successfully", user.username);
+    match bonus {
+        Some(bonus) => {
+            println!("{} ({who}) got their daily bonus of {bonus}", user.username);
+            Redirect::to("/?bonus=true").into_response()
+        }
+        None => Redirect::to("/").into_response(),
     }
 }
 
+pub async fn register(auth: Auth, mut session: WritableSession) -> impl IntoResponse {
+    if auth.current_user.is_some() {
+        return Redirect::to("/").into_response();
+    }
+    let mut context = tera::Context::new();
+    context.insert("csrf_token", &csrf::token(&mut session));
+    Html(TERA.render("register.html", &context).unwrap()).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct RegisterRequest {
+    username: String,
+    password: String,
+    confirm: String,
+    csrf_token: String,
+}
+
+fn register_error(request: &RegisterRequest, error: &str) -> Response {
+    let mut context = tera::Context::new();
+    context.insert("csrf_token", &request.csrf_token);
+    context.insert("username", &request.username);
+    context.insert("error", error);
+    (
+        StatusCode::BAD_REQUEST,
+        Html(TERA.render("register.html", &context).unwrap()),
+    )
+        .into_response()
+}
+
+pub async fn recieve_register(
+    mut auth: Auth,
+    ConnectInfo(who): ConnectInfo<SocketAddr>,
+    State((_state, database)): State<AppState>,
+    session: ReadableSession,
+    Form(request): Form<RegisterRequest>,
+) -> impl IntoResponse {
+    if !csrf::verify(&session, &request.csrf_token) {
+        println!("{who} tried to register without a valid CSRF token");
+        return error_403().into_response();
+    }
+    drop(session);
+    println!("{who} is trying to register as {}", request.username);
+    let username = match Username::new(&request.username) {
+        Ok(username) => username,
+        Err(e) => return register_error(&request, auth::username_error(e)),
+    };
+    if let Err(e) = auth::check_password_strength(&username, &request.password) {
+        return register_error(&request, e);
+    }
+    if request.password != request.confirm {
+        return register_error(&request, "Passwords do not match");
+    }
+
+    let password = request.password.clone();
+    let hash = tokio::task::spawn_blocking(move || auth::hash_password(&password))
+        .await
+        .unwrap();
+    let name = username.into_inner();
+    let conn = database.lock().await;
+    let result = sqlx::query!(
+        "INSERT INTO Users (username, password, balance)
+        VALUES (?, ?, ?)",
+        name,
+        hash,
+        auth::STARTING_BALANCE
+    )
+    .execute(&*conn)
+    .await;
+    let id = match result {
+        Ok(result) => result.last_insert_rowid(),
+        //SQLITE_CONSTRAINT_UNIQUE
+        Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some("2067") => {
+            println!("{who} tried to register the taken username {name}");
+            return register_error(&request, "That username is already taken");
+        }
+        Err(e) => panic!("Failed to create user {name}: {e}"),
+    };
+    drop(conn);
+
+    let user = User {
+        id,
+        username: name,
+        password: hash,
+        balance: auth::STARTING_BALANCE,
+        role: Role::Player,
+        banned: false,
+        display_name: None,
+        avatar: Avatar::default(),
+        on_leaderboards: true,
+    };
+    auth.login(&user).await.unwrap();
+    println!("{} ({who}) registered with id {id}", user.username);
+    Redirect::to("/").into_response()
+}
+
+#[derive(Deserialize)]
+pub struct CsrfForm {
+    csrf_token: String,
+}
+
 pub async fn logout(
     mut auth: Auth,
     ConnectInfo(who): ConnectInfo<SocketAddr>,
+    session: ReadableSession,
+    Form(form): Form<CsrfForm>,
 ) -> impl IntoResponse {
+    if !csrf::verify(&session, &form.csrf_token) {
+        println!("{who} tried to log out without a valid CSRF token");
+        return error_403().into_response();
+    }
+    drop(session);
     println!(
         "{} ({who}) is logging out",
         auth.current_user.clone().unwrap().username
     );
     auth.logout().await;
-    Redirect::to("/login")
+    Redirect::to("/login").into_response()
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Stakes {
+    #[default]
+    Low,
+    Medium,
+    High,
+}
+
+impl Stakes {
+    pub fn bets(self) -> (u32, u32) {
+        match self {
+            Stakes::Low => (10, 500),
+            Stakes::Medium => (50, 2000),
+            Stakes::High => (250, 10000),
+        }
+    }
+}
+
+/// How many people may watch a room without playing
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum SpectatorLimit {
+    #[default]
+    Unlimited,
+    Limited,
+    None,
+}
+
+impl SpectatorLimit {
+    fn max_spectators(self) -> Option<usize> {
+        match self {
+            SpectatorLimit::Unlimited => None,
+            SpectatorLimit::Limited => Some(10),
+            SpectatorLimit::None => Some(0),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CreateRoom {
+    csrf_token: String,
+    #[serde(default)]
+    stakes: Stakes,
+    decks: Option<u8>,
+    #[serde(default)]
+    dealer_hits_soft_17: bool,
+    #[serde(default)]
+    multi_seat: bool,
+    /// Serious tables don't give hints
+    #[serde(default)]
+    serious: bool,
+    #[serde(default)]
+    spectators: SpectatorLimit,
+    #[serde(default)]
+    visibility: Visibility,
+    #[serde(default)]
+    password: String,
+    /// A solo practice table, played with play money
+    #[serde(default)]
+    training: bool,
+    #[serde(default)]
+    count_quiz: bool,
+}
+
+impl CreateRoom {
+    fn rules(&self) -> Option<TableRules> {
+        let defaults = TableRules::default();
+        let decks = self.decks.unwrap_or(defaults.decks);
+        if ![1, 2, 6, 8].contains(&decks) {
+            return None;
+        }
+        let (min_bet, max_bet) = self.stakes.bets();
+        if self.training {
+            return Some(TableRules {
+                decks,
+                dealer_hits_soft_17: self.dealer_hits_soft_17,
+                min_bet,
+                max_bet,
+                max_players: 1,
+                max_spectators: Some(0),
+                ..defaults
+            });
+        }
+        Some(TableRules {
+            decks,
+            dealer_hits_soft_17: self.dealer_hits_soft_17,
+            min_bet,
+            max_bet,
+            max_seats_per_player: if self.multi_seat { 3 } else { 1 },
+            max_spectators: self.spectators.max_spectators(),
+            hints: !self.serious,
+            ..defaults
+        })
+    }
 }
 
 pub async fn create_room(
     ConnectInfo(who): ConnectInfo<SocketAddr>,
-    State((state, database)): State<(Arc<Mutex<MyState>>, Arc<Mutex<SqlitePool>>)>,
+    State((state, database)): State<AppState>,
     Extension(user): Extension<User>,
+    session: ReadableSession,
+    Form(form): Form<CreateRoom>,
 ) -> impl IntoResponse {
+    if !csrf::verify(&session, &form.csrf_token) {
+        println!(
+            "{} ({who}) tried to create a room without a valid CSRF token",
+            user.username
+        );
+        return error_403().into_response();
+    }
+    let Some(rules) = form.rules() else {
+        println!("{} ({who}) asked for invalid table rules", user.username);
+        return error_400().into_response();
+    };
+    //They'd only be able to watch their own table
+    if !form.training && user.balance < i64::from(rules.min_bet) {
+        println!(
+            "{} ({who}) can't afford to create a table at these stakes",
+            user.username
+        );
+        return error_400().into_response();
+    }
+    //Training tables are private, and there's nobody to invite
+    let visibility = if form.training {
+        Visibility::Private
+    } else {
+        form.visibility
+    };
+    //Private rooms without a password can only be joined by invite
+    let password_hash = if visibility == Visibility::Private && !form.password.is_empty() {
+        if form.password.chars().count() > 128 {
+            return error_400().into_response();
+        }
+        let password = form.password;
+        Some(
+            tokio::task::spawn_blocking(move || auth::hash_password(&password))
+                .await
+                .unwrap(),
+        )
+    } else {
+        None
+    };
     for _ in 0..10 {
         let id = new_id();
         println!(
             "{} ({who}) is attempting to create a room with id {id}",
             user.username
         );
-        let rooms = &mut state.lock().await.rooms;
-        if rooms.contains_key(&id) {
+        let mut lock = state.lock().await;
+        if lock.rooms.contains_key(&id) {
             println!("Room {id} already exists");
             continue;
         } else {
             println!("Created room {id}");
-            let room = Room::new(Card::shuffled_decks().into(), database);
-            rooms.insert(id.clone(), room);
-            return Redirect::to(&format!("/{id}"));
+            let access = Access::new(visibility, password_hash.clone(), user.id);
+            let mut room = Room::new(id.clone(), rules, access, database, lock.lobby.clone());
+            if form.training {
+                room.training = Some(Training::new(form.count_quiz));
+            }
+            room.publish();
+            lock.rooms.insert(id.clone(), room);
+            return Redirect::to(&format!("/{id}")).into_response();
         }
     }
     panic!("Failed to create a unique id");
 }
 
+#[derive(Deserialize)]
+pub struct JoinQuery {
+    invite: Option<String>,
+    #[serde(default)]
+    spectate: bool,
+    /// The seat to sit down in, if it's free
+    seat: Option<usize>,
+}
+
+fn room_password(id: &RoomId, csrf_token: &str, failed: bool) -> Response {
+    let mut context = tera::Context::new();
+    context.insert("id", &id.to_string());
+    context.insert("failed", &failed);
+    context.insert("csrf_token", csrf_token);
+    (
+        StatusCode::FORBIDDEN,
+        Html(TERA.render("room_password.html", &context).unwrap()),
+    )
+        .into_response()
+}
+
 pub async fn ingame(
     ConnectInfo(who): ConnectInfo<SocketAddr>,
     id: Option<Path<RoomId>>,
-    State((state, _database)): State<(Arc<Mutex<MyState>>, Arc<Mutex<SqlitePool>>)>,
+    Query(query): Query<JoinQuery>,
+    State((state, _database)): State<AppState>,
     Extension(user): Extension<User>,
+    mut session: WritableSession,
 ) -> impl IntoResponse {
     let Some(Path(id)) = id else {
         println!("{} ({who}) tried to join with an invalid id", user.username);
-        return (
-            StatusCode::BAD_REQUEST,
-            Html(TERA.render("400.html", &tera::Context::new()).unwrap())
-        );
+        return error_400().into_response();
     };
-    if let Some(room) = state.lock().await.rooms.get(&id) {
-        println!("{} ({who}) is trying to join game {id}", user.username);
-        if room.sockets.len() >= 6 || room.started {
-            //TODO: Error reporting
-            println!(
-                "Game with id {id} is too full for {} ({who})",
-                user.username
-            );
-            return (
-                StatusCode::BAD_REQUEST,
-                Html(TERA.render("400.html", &tera::Context::new()).unwrap()),
-            );
-        }
-    } else {
+    let mut lock = state.lock().await;
+    let Some(room) = lock.rooms.get_mut(&id) else {
         println!("{} ({who}) joined a game that doesn't exist", user.username);
-        return (
-            StatusCode::NOT_FOUND,
-            Html(TERA.render("404.html", &tera::Context::new()).unwrap()),
+        return error_404().await.into_response();
+    };
+    println!("{} ({who}) is trying to join game {id}", user.username);
+    if !room.access.is_allowed(user.id) {
+        match query.invite {
+            Some(token) if room.access.check_invite(&id, &token) => {
+                println!("{} ({who}) was invited to {id}", user.username);
+                room.access.allow(user.id);
+            }
+            _ => {
+                println!("{} ({who}) needs to be let into {id}", user.username);
+                return room_password(&id, &csrf::token(&mut session), false);
+            }
+        }
+    }
+    let broke = !room.can_cover_min_bet(&user);
+    let spectating = query.spectate || broke || !room.is_joinable();
+    if spectating && !room.can_spectate() {
+        //TODO: Error reporting
+        println!(
+            "Game with id {id} is too full for {} ({who})",
+            user.username
         );
+        return error_400().into_response();
     }
     let mut context = tera::Context::new();
-    context.insert("id", &id.into_inner());
-    context.insert("balance", &user.balance);
-    (
-        StatusCode::OK,
-        Html(TERA.render("game.html", &context).unwrap()),
-    )
+    context.insert("spectating", &spectating);
+    context.insert("broke", &broke);
+    context.insert("id", &id.to_string());
+    let balance = room
+        .training
+        .as_ref()
+        .map_or(user.balance, |training| training.balance);
+    context.insert("balance", &balance);
+    context.insert("training", &room.training.is_some());
+    context.insert("min_bet", &room.rules.min_bet);
+    context.insert("max_bet", &i64::from(room.rules.max_bet).min(balance));
+    context.insert("multi_seat", &(room.rules.max_seats_per_player > 1));
+    context.insert("is_moderator", &(user.role >= Role::Moderator));
+    context.insert("hints", &room.rules.hints);
+    context.insert("chat_max_length", &chat::MAX_LENGTH);
+    if room.access.visibility == Visibility::Private
+        && room.access.owner == user.id
+        && room.training.is_none()
+    {
+        context.insert("invite_link", &room.access.invite_link(&id));
+    }
+    drop(lock);
+    Html(TERA.render("game.html", &context).unwrap()).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct RoomPassword {
+    password: String,
+    csrf_token: String,
+}
+
+pub async fn recieve_room_password(
+    ConnectInfo(who): ConnectInfo<SocketAddr>,
+    Path(id): Path<RoomId>,
+    State((state, _database)): State<AppState>,
+    Extension(user): Extension<User>,
+    session: ReadableSession,
+    Form(form): Form<RoomPassword>,
+) -> impl IntoResponse {
+    if !csrf::verify(&session, &form.csrf_token) {
+        println!("{who} tried to join {id} without a valid CSRF token");
+        return error_403().into_response();
+    }
+    let hash = match state.lock().await.rooms.get(&id) {
+        Some(room) => room.access.password_hash().map(str::to_string),
+        None => return error_404().await.into_response(),
+    };
+    let Some(hash) = hash else {
+        //Invite only
+        return room_password(&id, &form.csrf_token, true);
+    };
+    let password = form.password;
+    let verified = tokio::task::spawn_blocking(move || auth::verify_password(&hash, &password))
+        .await
+        .unwrap();
+    if verified != Verified::Valid {
+        println!("{} ({who}) gave the wrong password for {id}", user.username);
+        return room_password(&id, &form.csrf_token, true);
+    }
+    match state.lock().await.rooms.get_mut(&id) {
+        Some(room) => room.access.allow(user.id),
+        None => return error_404().await.into_response(),
+    }
+    println!("{} ({who}) gave the password for {id}", user.username);
+    Redirect::to(&format!("/{id}")).into_response()
 }
 
 pub async fn ws_handler(
     ws: Option<WebSocketUpgrade>,
     Path(id): Path<RoomId>,
     ConnectInfo(who): ConnectInfo<SocketAddr>,
-    State((state, _database)): State<(Arc<Mutex<MyState>>, Arc<Mutex<SqlitePool>>)>,
+    headers: HeaderMap,
+    Query(query): Query<JoinQuery>,
+    State((state, _database)): State<AppState>,
     Extension(user): Extension<User>,
 ) -> impl IntoResponse {
     let Some(ws) = ws else {
         println!("{} ({who}) tried to load the websocket page", user.username);
         return (
             StatusCode::BAD_REQUEST,
-            Html(TERA.render("400.html", &tera::Context::new()).unwrap())
-        ).into_response();
+            Html(TERA.render("400.html", &tera::Context::new()).unwrap()),
+        )
+            .into_response();
+    };
+    if !csrf::same_origin(&headers) {
+        println!(
+            "{} ({who}) tried to open a websocket from another origin",
+            user.username
+        );
+        return error_403().into_response();
+    }
+    //Anyone who can't take a seat watches instead, if there's room for them
+    let joining = match state.lock().await.rooms.get(&id) {
+        Some(room) if !room.access.is_allowed(user.id) => return error_403().into_response(),
+        Some(room) if !query.spectate && room.is_joinable() && room.can_cover_min_bet(&user) => {
+            Joining::Seat(query.seat)
+        }
+        Some(room) if room.can_spectate() => Joining::Spectate,
+        Some(_) => return error_400().into_response(),
+        None => return error_404().await.into_response(),
     };
-    ws.on_upgrade(move |socket| websocket(socket, who, id, state, user))
+    ws.on_upgrade(move |socket| websocket(socket, who, id, state, user, joining))
+}
+
+pub fn error_400() -> impl IntoResponse {
+    (
+        StatusCode::BAD_REQUEST,
+        Html(TERA.render("400.html", &tera::Context::new()).unwrap()),
+    )
+}
+
+pub fn error_403() -> impl IntoResponse {
+    (
+        StatusCode::FORBIDDEN,
+        Html(TERA.render("403.html", &tera::Context::new()).unwrap()),
+    )
 }
 
 pub async fn error_404() -> impl IntoResponse {
@@ -190,6 +682,13 @@ pub async fn error_404() -> impl IntoResponse {
     )
 }
 
+pub fn error_429() -> impl IntoResponse {
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        Html(TERA.render("429.html", &tera::Context::new()).unwrap()),
+    )
+}
+
 pub fn error_500() -> impl IntoResponse {
     (
         StatusCode::INTERNAL_SERVER_ERROR,
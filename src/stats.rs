@@ -0,0 +1,137 @@
+//! Lifetime statistics for a player, worked out from the rounds and ledger
+//! kept for them
+
+use serde::Serialize;
+use sqlx::SqlitePool;
+
+/// The most points the balance chart is drawn from
+const MAX_POINTS: i64 = 1000;
+
+/// How a set of hands turned out
+#[derive(Debug, Default, Serialize)]
+pub struct Outcomes {
+    pub hands: i64,
+    pub wins: i64,
+    pub losses: i64,
+    pub pushes: i64,
+    pub blackjacks: i64,
+    /// Won or lost on these hands, not counting stakes returned
+    pub net: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    pub rounds: i64,
+    pub hands: Outcomes,
+    /// The most won in a single round
+    pub biggest_win: i64,
+    pub average_bet: f64,
+    pub splits: i64,
+    /// The hands split pairs were played as
+    pub split_hands: Outcomes,
+}
+
+/// The balance after a change to it, and when it happened
+#[derive(Debug, Serialize)]
+pub struct BalancePoint {
+    pub at: i64,
+    pub balance: i64,
+}
+
+/// Everything `user_id` has played for real, leaving out training tables
+pub async fn of(conn: &SqlitePool, user_id: i64) -> Stats {
+    let hands = sqlx::query_as!(
+        Outcomes,
+        r#"SELECT COUNT(*) AS "hands!: i64",
+            COALESCE(SUM(RoundHands.result = 'Win'), 0) AS "wins!: i64",
+            COALESCE(SUM(RoundHands.result = 'Lose'), 0) AS "losses!: i64",
+            COALESCE(SUM(RoundHands.result = 'Push'), 0) AS "pushes!: i64",
+            COALESCE(SUM(RoundHands.result = 'Blackjack'), 0) AS "blackjacks!: i64",
+            COALESCE(SUM(RoundHands.payout - RoundHands.bet), 0) AS "net!: i64"
+        FROM RoundHands
+        JOIN Rounds ON Rounds.id = RoundHands.round_id
+        WHERE RoundHands.user_id = ? AND NOT Rounds.training"#,
+        user_id
+    )
+    .fetch_one(conn)
+    .await
+    .unwrap();
+    let rounds = sqlx::query!(
+        r#"SELECT COUNT(*) AS "rounds!: i64",
+            MAX(COALESCE(MAX(net), 0), 0) AS "biggest_win!: i64",
+            COALESCE(SUM(bet) / CAST(SUM(hands) AS real), 0.0) AS "average_bet!: f64"
+        FROM (
+            SELECT SUM(RoundHands.payout - RoundHands.bet) AS net, SUM(RoundHands.bet) AS bet,
+                COUNT(*) AS hands
+            FROM RoundHands
+            JOIN Rounds ON Rounds.id = RoundHands.round_id
+            WHERE RoundHands.user_id = ? AND NOT Rounds.training
+            GROUP BY RoundHands.round_id
+        )"#,
+        user_id
+    )
+    .fetch_one(conn)
+    .await
+    .unwrap();
+    let splits = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "splits!: i64"
+        FROM RoundActions
+        JOIN Rounds ON Rounds.id = RoundActions.round_id
+        WHERE RoundActions.user_id = ? AND RoundActions.action = 'split' AND NOT Rounds.training"#,
+        user_id
+    )
+    .fetch_one(conn)
+    .await
+    .unwrap();
+    //A seat that ended the round with a second hand had split, so both of its
+    //hands count
+    let split_hands = sqlx::query_as!(
+        Outcomes,
+        r#"SELECT COUNT(*) AS "hands!: i64",
+            COALESCE(SUM(RoundHands.result = 'Win'), 0) AS "wins!: i64",
+            COALESCE(SUM(RoundHands.result = 'Lose'), 0) AS "losses!: i64",
+            COALESCE(SUM(RoundHands.result = 'Push'), 0) AS "pushes!: i64",
+            COALESCE(SUM(RoundHands.result = 'Blackjack'), 0) AS "blackjacks!: i64",
+            COALESCE(SUM(RoundHands.payout - RoundHands.bet), 0) AS "net!: i64"
+        FROM RoundHands
+        JOIN Rounds ON Rounds.id = RoundHands.round_id
+        WHERE RoundHands.user_id = ? AND NOT Rounds.training
+            AND EXISTS (
+                SELECT 1 FROM RoundHands AS Split
+                WHERE Split.round_id = RoundHands.round_id AND Split.seat = RoundHands.seat
+                    AND Split.second_hand
+            )"#,
+        user_id
+    )
+    .fetch_one(conn)
+    .await
+    .unwrap();
+    Stats {
+        rounds: rounds.rounds,
+        hands,
+        biggest_win: rounds.biggest_win,
+        average_bet: rounds.average_bet,
+        splits,
+        split_hands,
+    }
+}
+
+/// How `user_id`'s balance has moved, oldest first, from their most recent
+/// ledger entries
+pub async fn balance_history(conn: &SqlitePool, user_id: i64) -> Vec<BalancePoint> {
+    let mut points = sqlx::query_as!(
+        BalancePoint,
+        r#"SELECT created_at AS "at!", balance_after AS "balance!"
+        FROM Ledger
+        WHERE user_id = ?
+        ORDER BY id DESC
+        LIMIT ?"#,
+        user_id,
+        MAX_POINTS
+    )
+    .fetch_all(conn)
+    .await
+    .unwrap();
+    points.reverse();
+    points
+}
@@ -0,0 +1,422 @@
+use serde::Serialize;
+
+use crate::{
+    card::{Card, Rank},
+    engine::{Hand, TableRules},
+};
+
+/// What basic strategy says to do with a hand
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Advice {
+    Hit,
+    Stand,
+    Double,
+    Split,
+    Surrender,
+}
+
+/// The best play for a hand, and why
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hint {
+    pub advice: Advice,
+    /// The best play when the table doesn't allow `advice`. The same as
+    /// `advice` for hitting and standing
+    pub otherwise: Advice,
+    pub reason: String,
+}
+
+/// The dealer's upcard, counting an ace as 11
+fn upcard_value(upcard: Card) -> u8 {
+    match upcard.rank {
+        Rank::Ace => 11,
+        _ => upcard.score_card(),
+    }
+}
+
+fn upcard_name(up: u8) -> String {
+    match up {
+        11 => "an ace".to_string(),
+        10 => "a 10".to_string(),
+        8 => "an 8".to_string(),
+        n => format!("a {n}"),
+    }
+}
+
+/// The play basic strategy makes, what to do if it isn't allowed, and why
+type Play = (Advice, Advice, &'static str);
+
+fn pair(rank: u8, up: u8, rules: &TableRules) -> Option<Play> {
+    let das = rules.double_after_split;
+    let split = match rank {
+        1 => true,
+        8 => true,
+        9 => (2..=9).contains(&up) && up != 7,
+        7 => up <= 7 || (rules.decks == 1 && up == 8),
+        6 => (3..=6).contains(&up) || (das && up == 2),
+        4 => das && (up == 5 || up == 6),
+        2 | 3 => (4..=7).contains(&up) || (das && up <= 3),
+        //Fives play as a hard 10 and tens as a hard 20
+        _ => return None,
+    };
+    if split {
+        let why = match rank {
+            1 => "two hands starting with an ace beat one soft 12",
+            8 => "16 is the worst total there is, and each 8 makes a fair start",
+            _ if up <= 6 => "the dealer is weak, so it pays to have more money out",
+            _ => "two hands each starting with this card do better than the pair",
+        };
+        return Some((Advice::Split, Advice::Hit, why));
+    }
+    match rank {
+        9 => Some((Advice::Stand, Advice::Stand, "18 is strong enough here")),
+        _ => None,
+    }
+}
+
+fn soft(total: u8, up: u8, rules: &TableRules) -> Play {
+    let h17 = rules.dealer_hits_soft_17;
+    let double = match total {
+        13 | 14 => up == 5 || up == 6,
+        15 | 16 => (4..=6).contains(&up),
+        17 => (3..=6).contains(&up),
+        18 => (3..=6).contains(&up) || (h17 && up == 2),
+        19 => h17 && up == 6,
+        _ => false,
+    };
+    match total {
+        _ if double && total >= 18 => (
+            Advice::Double,
+            Advice::Stand,
+            "the dealer is likely to bust, and you can't bust taking one card",
+        ),
+        _ if double => (
+            Advice::Double,
+            Advice::Hit,
+            "the dealer is likely to bust, and you can't bust taking one card",
+        ),
+        19.. => (
+            Advice::Stand,
+            Advice::Stand,
+            "you already have a strong hand",
+        ),
+        18 if up <= 8 => (
+            Advice::Stand,
+            Advice::Stand,
+            "18 beats what the dealer usually makes from here",
+        ),
+        _ => (
+            Advice::Hit,
+            Advice::Hit,
+            "you can't bust taking one card, and this total rarely wins",
+        ),
+    }
+}
+
+fn hard(total: u8, up: u8, rules: &TableRules) -> Play {
+    let h17 = rules.dealer_hits_soft_17;
+    let few_decks = rules.decks <= 2;
+    let surrender = match total {
+        16 => up >= 9 && !(few_decks && up == 9),
+        15 => up == 10 || (h17 && up == 11),
+        17 => h17 && up == 11,
+        _ => false,
+    };
+    let surrender_otherwise = if total == 17 {
+        Advice::Stand
+    } else {
+        Advice::Hit
+    };
+    let double = match total {
+        11 => up <= 10 || h17 || few_decks,
+        10 => up <= 9,
+        9 => (3..=6).contains(&up) || (few_decks && up == 2),
+        8 => rules.decks == 1 && (up == 5 || up == 6),
+        _ => false,
+    };
+    if surrender {
+        return (
+            Advice::Surrender,
+            surrender_otherwise,
+            "you'll lose more often than not, and giving up half is cheaper",
+        );
+    }
+    if double {
+        return (
+            Advice::Double,
+            Advice::Hit,
+            "you're more likely than the dealer to end up with a strong hand",
+        );
+    }
+    match total {
+        17.. => (
+            Advice::Stand,
+            Advice::Stand,
+            "most cards would bust you, and 17 or more often holds up",
+        ),
+        13..=16 if up <= 6 => (
+            Advice::Stand,
+            Advice::Stand,
+            "the dealer is likely to bust, so don't risk it yourself",
+        ),
+        12 if (4..=6).contains(&up) => (
+            Advice::Stand,
+            Advice::Stand,
+            "the dealer is likely to bust, so don't risk it yourself",
+        ),
+        12..=16 => (
+            Advice::Hit,
+            Advice::Hit,
+            "the dealer will probably make a hand that beats this one",
+        ),
+        _ => (Advice::Hit, Advice::Hit, "no card can bust you"),
+    }
+}
+
+/// Works out what basic strategy says to do with `hand`, given the dealer's
+/// upcard and the rules of the table
+pub fn advise(hand: &Hand, upcard: Card, rules: &TableRules) -> Hint {
+    let up = upcard_value(upcard);
+    let first_two = hand.cards.len() == 2;
+    //Doubling after splitting is up to the table, and surrendering never is
+    let can_double = first_two && (!hand.is_split() || rules.double_after_split);
+    let can_surrender = first_two && !hand.is_split();
+    let total = hand.score().total();
+
+    let (hand_name, play) = if hand.score().is_blackjack() {
+        (
+            "Blackjack".to_string(),
+            (Advice::Stand, Advice::Stand, "you've won already"),
+        )
+    } else if let Some(play) = hand
+        .can_split()
+        .then(|| pair(hand.cards[0].score_card(), up, rules))
+        .flatten()
+    {
+        let name = match hand.cards[0].score_card() {
+            1 => "A pair of aces".to_string(),
+            n => format!("A pair of {n}s"),
+        };
+        (name, play)
+    } else if hand.is_soft() {
+        (format!("Soft {total}"), soft(total, up, rules))
+    } else {
+        (format!("Hard {total}"), hard(total, up, rules))
+    };
+    let (advice, otherwise, why) = play;
+    let allowed = match advice {
+        Advice::Double => can_double,
+        Advice::Surrender => can_surrender,
+        _ => true,
+    };
+    let advice = if allowed { advice } else { otherwise };
+    Hint {
+        advice,
+        otherwise,
+        reason: format!("{hand_name} against {}: {why}", upcard_name(up)),
+    }
+}
+
+/// What a player does with their hand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Hit,
+    Stand,
+    Split,
+}
+
+/// How a bot or a simulated player plays. It only gets to see what a player
+/// sitting at the table would
+pub trait Strategy: Sync {
+    /// How much to put on a new hand
+    fn bet(&self, rules: &TableRules) -> u32 {
+        rules.min_bet
+    }
+
+    fn decide(&self, hand: &Hand, upcard: Card, rules: &TableRules) -> Decision;
+}
+
+/// Plays by the book. These tables don't offer doubling or surrender, so it
+/// falls back to what the book says to do instead
+pub struct BasicStrategy;
+
+impl Strategy for BasicStrategy {
+    fn decide(&self, hand: &Hand, upcard: Card, rules: &TableRules) -> Decision {
+        let hint = advise(hand, upcard, rules);
+        let advice = match hint.advice {
+            Advice::Double | Advice::Surrender => hint.otherwise,
+            advice => advice,
+        };
+        match advice {
+            Advice::Split => Decision::Split,
+            Advice::Stand => Decision::Stand,
+            _ => Decision::Hit,
+        }
+    }
+}
+
+/// Only ever takes a card that can't bust it
+pub struct NeverBust;
+
+impl Strategy for NeverBust {
+    fn decide(&self, hand: &Hand, _upcard: Card, _rules: &TableRules) -> Decision {
+        if hand.score().total() <= 11 || (hand.is_soft() && hand.score().total() < 18) {
+            Decision::Hit
+        } else {
+            Decision::Stand
+        }
+    }
+}
+
+/// Flips a coin, and bets anything the table allows
+pub struct Random;
+
+impl Strategy for Random {
+    fn bet(&self, rules: &TableRules) -> u32 {
+        fastrand::u32(rules.min_bet..=rules.max_bet)
+    }
+
+    fn decide(&self, hand: &Hand, _upcard: Card, _rules: &TableRules) -> Decision {
+        if hand.can_split() && fastrand::bool() {
+            Decision::Split
+        } else if fastrand::bool() {
+            Decision::Hit
+        } else {
+            Decision::Stand
+        }
+    }
+}
+
+/// Plays by the same rules as the dealer
+pub struct DealerMimic;
+
+impl Strategy for DealerMimic {
+    fn decide(&self, hand: &Hand, _upcard: Card, rules: &TableRules) -> Decision {
+        let total = hand.score().total();
+        if total < 17 || (total == 17 && hand.is_soft() && rules.dealer_hits_soft_17) {
+            Decision::Hit
+        } else {
+            Decision::Stand
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::Suit;
+
+    fn hand(ranks: &[Rank]) -> Hand {
+        Hand::new(ranks.iter().map(|&rank| card(rank)).collect(), false)
+    }
+
+    fn card(rank: Rank) -> Card {
+        Card {
+            suit: Suit::Spades,
+            rank,
+        }
+    }
+
+    fn advice(ranks: &[Rank], up: Rank) -> Hint {
+        advise(&hand(ranks), card(up), &TableRules::default())
+    }
+
+    #[test]
+    fn soft_18_hits_a_9() {
+        assert_eq!(
+            advice(&[Rank::Ace, Rank::Seven], Rank::Nine).advice,
+            Advice::Hit
+        );
+    }
+
+    #[test]
+    fn eights_split_against_a_10() {
+        assert_eq!(
+            advice(&[Rank::Eight, Rank::Eight], Rank::King).advice,
+            Advice::Split
+        );
+    }
+
+    #[test]
+    fn hard_12_stands_on_a_4() {
+        assert_eq!(
+            advice(&[Rank::Ten, Rank::Two], Rank::Four).advice,
+            Advice::Stand
+        );
+    }
+
+    #[test]
+    fn hard_16_surrenders_to_a_10_or_hits() {
+        let hint = advice(&[Rank::Ten, Rank::Six], Rank::Ten);
+        assert_eq!(hint.advice, Advice::Surrender);
+        assert_eq!(hint.otherwise, Advice::Hit);
+        let decision = BasicStrategy.decide(
+            &hand(&[Rank::Ten, Rank::Six]),
+            card(Rank::Ten),
+            &TableRules::default(),
+        );
+        assert_eq!(decision, Decision::Hit);
+    }
+
+    #[test]
+    fn no_doubling_after_splitting_unless_allowed() {
+        let rules = TableRules {
+            double_after_split: false,
+            ..TableRules::default()
+        };
+        let mut first = hand(&[Rank::Six, Rank::Six]);
+        first.split();
+        first.cards.push(card(Rank::Five));
+        assert_eq!(advise(&first, card(Rank::Six), &rules).advice, Advice::Hit);
+        assert_eq!(
+            advise(&first, card(Rank::Six), &TableRules::default()).advice,
+            Advice::Double
+        );
+    }
+
+    #[test]
+    fn never_bust_only_hits_when_it_cant_bust() {
+        let rules = TableRules::default();
+        let up = card(Rank::Ten);
+        assert_eq!(
+            NeverBust.decide(&hand(&[Rank::Nine, Rank::Two]), up, &rules),
+            Decision::Hit
+        );
+        assert_eq!(
+            NeverBust.decide(&hand(&[Rank::Ten, Rank::Two]), up, &rules),
+            Decision::Stand
+        );
+        assert_eq!(
+            NeverBust.decide(&hand(&[Rank::Ace, Rank::Six]), up, &rules),
+            Decision::Hit
+        );
+    }
+
+    #[test]
+    fn dealer_mimic_follows_the_soft_17_rule() {
+        let soft_17 = hand(&[Rank::Ace, Rank::Six]);
+        let up = card(Rank::Ten);
+        let h17 = TableRules {
+            dealer_hits_soft_17: true,
+            ..TableRules::default()
+        };
+        assert_eq!(DealerMimic.decide(&soft_17, up, &h17), Decision::Hit);
+        assert_eq!(
+            DealerMimic.decide(&soft_17, up, &TableRules::default()),
+            Decision::Stand
+        );
+        assert_eq!(
+            DealerMimic.decide(&hand(&[Rank::Ten, Rank::Six]), up, &h17),
+            Decision::Hit
+        );
+    }
+
+    #[test]
+    fn random_only_splits_pairs_and_bets_within_the_limits() {
+        let rules = TableRules::default();
+        for _ in 0..100 {
+            let decision = Random.decide(&hand(&[Rank::Ten, Rank::Six]), card(Rank::Two), &rules);
+            assert_ne!(decision, Decision::Split);
+            assert!((rules.min_bet..=rules.max_bet).contains(&Random.bet(&rules)));
+        }
+    }
+}
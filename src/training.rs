@@ -0,0 +1,183 @@
+//! Solo practice tables, played with play money and marked against basic
+//! strategy
+
+use serde::Serialize;
+
+use crate::{
+    card::Card,
+    engine::{Hand, TableRules},
+    strategy::{self, Advice, BasicStrategy, Decision, Strategy},
+};
+
+/// What a training session starts with, which has nothing to do with the
+/// player's real balance
+pub const PLAY_MONEY: i64 = 5000;
+/// One round in this many ends by asking for the running count
+const COUNT_QUIZ_ODDS: u32 = 3;
+
+/// How a training session has gone so far
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TrainingStats {
+    pub decisions: u32,
+    /// Decisions that matched basic strategy
+    pub correct: u32,
+    pub counts_asked: u32,
+    pub counts_correct: u32,
+    /// Play money won or lost
+    pub net: i64,
+}
+
+pub struct Training {
+    /// The play money the player bets from
+    pub balance: i64,
+    /// Whether to ask for the running count now and then
+    count_quiz: bool,
+    /// The running count the player has been asked for and not answered
+    asked_count: Option<i32>,
+    stats: TrainingStats,
+}
+
+impl Training {
+    pub fn new(count_quiz: bool) -> Self {
+        Self {
+            balance: PLAY_MONEY,
+            count_quiz,
+            asked_count: None,
+            stats: TrainingStats::default(),
+        }
+    }
+
+    /// Marks a decision against basic strategy, returning whether it was
+    /// right and what the book says. These tables don't offer doubling or
+    /// surrender, so it's marked against what to do instead
+    pub fn grade(
+        &mut self,
+        hand: &Hand,
+        upcard: Card,
+        rules: &TableRules,
+        decision: Decision,
+    ) -> (bool, Advice, String) {
+        let hint = strategy::advise(hand, upcard, rules);
+        let advice = match hint.advice {
+            Advice::Double | Advice::Surrender => hint.otherwise,
+            advice => advice,
+        };
+        let correct = BasicStrategy.decide(hand, upcard, rules) == decision;
+        self.stats.decisions += 1;
+        self.stats.correct += u32::from(correct);
+        (correct, advice, hint.reason)
+    }
+
+    /// Sometimes asks for the running count once a round is over, remembering
+    /// the answer. Returns whether to ask
+    pub fn maybe_ask_count(&mut self, running: i32) -> bool {
+        if !self.count_quiz || fastrand::u32(0..COUNT_QUIZ_ODDS) != 0 {
+            return false;
+        }
+        self.asked_count = Some(running);
+        self.stats.counts_asked += 1;
+        true
+    }
+
+    /// Checks an answer to the last count asked for, returning whether it was
+    /// right and what the count was, or nothing if it wasn't asked for
+    pub fn answer_count(&mut self, count: i32) -> Option<(bool, i32)> {
+        let running = self.asked_count.take()?;
+        let correct = count == running;
+        self.stats.counts_correct += u32::from(correct);
+        Some((correct, running))
+    }
+
+    pub fn stats(&self) -> TrainingStats {
+        TrainingStats {
+            net: self.balance - PLAY_MONEY,
+            ..self.stats.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::{Rank, Suit};
+
+    fn card(rank: Rank) -> Card {
+        Card {
+            suit: Suit::Diamonds,
+            rank,
+        }
+    }
+
+    fn hand(ranks: &[Rank]) -> Hand {
+        Hand::new(ranks.iter().map(|&rank| card(rank)).collect(), false)
+    }
+
+    #[test]
+    fn doubles_are_graded_as_what_to_do_instead() {
+        let mut training = Training::new(false);
+        let eleven = hand(&[Rank::Six, Rank::Five]);
+        let rules = TableRules::default();
+        let (correct, advice, _) = training.grade(&eleven, card(Rank::Six), &rules, Decision::Hit);
+        assert!(correct);
+        assert_eq!(advice, Advice::Hit);
+        let (correct, _, _) = training.grade(&eleven, card(Rank::Six), &rules, Decision::Stand);
+        assert!(!correct);
+    }
+
+    #[test]
+    fn surrenders_are_graded_as_what_to_do_instead() {
+        let mut training = Training::new(false);
+        let sixteen = hand(&[Rank::Ten, Rank::Six]);
+        let rules = TableRules::default();
+        let (correct, advice, reason) =
+            training.grade(&sixteen, card(Rank::King), &rules, Decision::Hit);
+        assert!(correct);
+        assert_eq!(advice, Advice::Hit);
+        assert!(reason.starts_with("Hard 16 against a 10"), "{reason}");
+    }
+
+    #[test]
+    fn stats_add_up() {
+        let mut training = Training::new(true);
+        let rules = TableRules::default();
+        let twelve = hand(&[Rank::Ten, Rank::Two]);
+        for decision in [Decision::Stand, Decision::Hit, Decision::Stand] {
+            training.grade(&twelve, card(Rank::Four), &rules, decision);
+        }
+        training.balance -= 30;
+
+        assert_eq!(training.answer_count(0), None);
+        training.asked_count = Some(3);
+        training.stats.counts_asked += 1;
+        assert_eq!(training.answer_count(3), Some((true, 3)));
+        //Each count can only be answered once
+        assert_eq!(training.answer_count(3), None);
+        training.asked_count = Some(-2);
+        training.stats.counts_asked += 1;
+        assert_eq!(training.answer_count(2), Some((false, -2)));
+
+        assert_eq!(
+            training.stats(),
+            TrainingStats {
+                decisions: 3,
+                correct: 2,
+                counts_asked: 2,
+                counts_correct: 1,
+                net: -30,
+            }
+        );
+    }
+
+    #[test]
+    fn only_quizzes_when_asked_to() {
+        let mut quiet = Training::new(false);
+        assert!((0..100).all(|_| !quiet.maybe_ask_count(5)));
+        assert_eq!(quiet.stats().counts_asked, 0);
+
+        let mut quizzed = Training::new(true);
+        let asked = (0..100).filter(|_| quizzed.maybe_ask_count(5)).count();
+        assert!(asked > 0);
+        assert_eq!(quizzed.stats().counts_asked, asked as u32);
+        assert_eq!(quizzed.answer_count(5), Some((true, 5)));
+    }
+}
@@ -2,7 +2,20 @@ window.onload = function() {
   let end_turn_button = document.getElementById("endturn");
   let deal_button = document.getElementById("deal");
   let split_button = document.getElementById("split");
-  let ws = new WebSocket("ws://localhost:3000" + window.location.pathname + "/ws");
+  let start_button = document.getElementById("start");
+  let hint_button = document.getElementById("hint");
+  let scheme = window.location.protocol === "https:" ? "wss://" : "ws://";
+  let spectating = document.getElementById("spectating") !== null;
+  let params = new URLSearchParams(window.location.search);
+  let query = spectating ? "?spectate=true" : "";
+  //Replays can be watched faster than they were played
+  if (spectating && params.has("speed")) {
+    query += "&speed=" + params.get("speed");
+  }
+  if (!spectating && params.has("seat")) {
+    query = "?seat=" + params.get("seat");
+  }
+  let ws = new WebSocket(scheme + window.location.host + window.location.pathname + "/ws" + query);
   ws.onopen = function() {
     console.log("Connection Made");
     end_turn_button.onclick = function() {
@@ -18,27 +31,282 @@ window.onload = function() {
       split_button.hidden = true;
       split_button.disabled = true;
     }
+    if (hint_button) {
+      hint_button.onclick = function() {
+        ws.send(JSON.stringify("Hint"));
+        hint_button.disabled = true;
+      }
+    }
+    start_button.onclick = function() {
+      ws.send(JSON.stringify("GameStart"));
+      start_button.disabled = true;
+      start_button.hidden = true;
+    }
+    let end_training = document.getElementById("end_training");
+    if (end_training) {
+      end_training.onclick = function() {
+        ws.send(JSON.stringify("EndTraining"));
+      }
+      document.getElementById("count_submit").onclick = function() {
+        let count = Number(document.getElementById("count_answer").value);
+        ws.send(JSON.stringify({"CountAnswer":{"count":count}}));
+        document.getElementById("count_quiz").hidden = true;
+      }
+    }
+    let revoke_button = document.getElementById("revoke");
+    if (revoke_button) {
+      revoke_button.onclick = function() {
+        ws.send(JSON.stringify("RevokeInvites"));
+      }
+    }
+    let chat_send = document.getElementById("chat_send");
+    if (chat_send) {
+      let chat_text = document.getElementById("chat_text");
+      chat_send.onclick = function() {
+        if (chat_text.value.trim() !== "") {
+          ws.send(JSON.stringify({"Chat":{"text":chat_text.value}}));
+          chat_text.value = "";
+        }
+      }
+      chat_text.onkeydown = function(event) {
+        if (event.key === "Enter") {
+          chat_send.onclick();
+        }
+      }
+    }
+    let mod_controls = document.getElementById("mod_controls");
+    if (mod_controls && !spectating) {
+      mod_controls.removeAttribute("hidden");
+      document.getElementById("mute").onclick = function() {
+        let seat = Number(document.getElementById("mute_seat").value);
+        let minutes = Number(document.getElementById("mute_minutes").value);
+        ws.send(JSON.stringify({"MuteChat":{"seat":seat,"minutes":minutes}}));
+      }
+    }
+    let seat_choice = document.getElementById("seat_choice");
+    document.getElementById("move_seat").onclick = function() {
+      ws.send(JSON.stringify({"ChangeSeat":{"from":my_seats[0],"to":Number(seat_choice.value)}}));
+    }
+    let take_seat = document.getElementById("take_seat");
+    if (take_seat) {
+      take_seat.onclick = function() {
+        ws.send(JSON.stringify({"TakeSeat":{"seat":Number(seat_choice.value)}}));
+      }
+      document.getElementById("leave_seat").onclick = function() {
+        ws.send(JSON.stringify({"LeaveSeat":{"seat":Number(seat_choice.value)}}));
+      }
+    }
+  }
+  //Where each hand on the table is drawn, by its id
+  let hands = {};
+  let my_seats = [];
+  let is_host = false;
+  let playing = false;
+  let locked = false;
+
+  function card_image(card) {
+    let img = document.createElement("img");
+    if (card !== null) {
+      img.src = "/static/cards/" + card.rank + card.suit + ".svg";
+    } else {
+      img.src = "/static/cards/back.svg";
+    }
+    img.style = "width: 20%;";
+    return img;
+  }
+
+  function seat_hand(seat, second_hand) {
+    return document.getElementById("player" + seat + (second_hand ? ".1" : ""));
+  }
+
+  const avatars = {
+    "spade": "\u2660", "heart": "\u2665", "diamond": "\u2666", "club": "\u2663",
+    "star": "\u2605", "crown": "\u265B", "dice": "\u2684", "chip": "\u26C0",
+  };
+
+  //People this browser has chosen not to hear from, by username
+  let ignored = JSON.parse(localStorage.getItem("ignored") || "[]");
+
+  function show_chat(message) {
+    if (ignored.includes(message.from.username)) {
+      return;
+    }
+    let log = document.getElementById("chat_log");
+    let line = document.createElement("p");
+    let time = new Date(message.at * 1000).toLocaleTimeString();
+    let name = document.createElement("b");
+    name.textContent = avatars[message.from.avatar] + " " + message.from.display_name;
+    name.title = message.from.username;
+    line.append(time + " ", name, ": " + message.text + " ");
+    if (!spectating) {
+      let report = document.createElement("button");
+      report.textContent = "Report";
+      report.onclick = function() {
+        ws.send(JSON.stringify({"ReportChat":{"id":message.id}}));
+      }
+      let ignore = document.createElement("button");
+      ignore.textContent = "Ignore";
+      ignore.onclick = function() {
+        ignored.push(message.from.username);
+        localStorage.setItem("ignored", JSON.stringify(ignored));
+        line.remove();
+      }
+      line.append(report, ignore);
+    }
+    log.appendChild(line);
+    log.scrollTop = log.scrollHeight;
+  }
+
+  function show_player(seat, player) {
+    let name = document.getElementById("seat" + seat + "_name");
+    if (player === null) {
+      name.textContent = "";
+      name.title = "";
+    } else {
+      name.textContent = avatars[player.avatar] + " " + player.display_name;
+      name.title = player.username;
+    }
+  }
+
+  //Adds up the bets on every hand played from `seat`
+  function show_bet(seat) {
+    let total = 0;
+    for (let id in hands) {
+      if (hands[id].seat === seat) {
+        total += hands[id].bet;
+      }
+    }
+    document.getElementById("seat" + seat + "_bet").textContent = total > 0 ? "(bet " + total + ")" : "";
   }
-  let player_count = 0;
+
+  function hand_div(id) {
+    let hand = hands[id];
+    return seat_hand(hand.seat, hand.second_hand);
+  }
+
+  function clear_table() {
+    document.getElementById("dealer").innerHTML = "";
+    for (let seat = 0; seat < 6; seat++) {
+      seat_hand(seat, false).innerHTML = "";
+      seat_hand(seat, true).innerHTML = "";
+      seat_hand(seat, true).hidden = true;
+    }
+  }
+
+  //Seats can only be changed while no round is being played
+  function show_seat_controls() {
+    let controls = document.getElementById("seat_controls");
+    if (controls) {
+      controls.hidden = playing || spectating;
+    }
+  }
+
+  function show_start_button() {
+    start_button.hidden = !is_host || playing;
+    start_button.disabled = !is_host || playing;
+  }
+
   ws.onmessage = function(event) {
     let msg = JSON.parse(event.data);
     console.log(msg);
     if (msg === "EndTurn") {
 
       deal_button.disabled = true;
+      if (hint_button) {
+        hint_button.disabled = true;
+      }
+
+    } else if (msg === "Kicked" || msg === "RoomClosed") {
 
-    } else if (msg === "NewHost") {
+      alert(msg === "Kicked" ? "You have been removed from the room." : "This room has been closed. Any bets have been refunded.");
+      location.href = "/";
 
-      let start_button = document.getElementById("start");
-      start_button.removeAttribute("hidden");
-      start_button.disabled = false;
-      start_button.onclick = function() {
-        ws.send(JSON.stringify("GameStart"));
-        start_button.disabled = true;
-        start_button.hidden = true;
+    } else if (msg === "CountQuiz") {
+
+      document.getElementById("count_result").textContent = "";
+      document.getElementById("count_quiz").removeAttribute("hidden");
+
+    } else if (msg.hasOwnProperty('Graded')) {
+
+      let graded = msg.Graded;
+      document.getElementById("grade").textContent = (graded.correct ? "Right. " : "Basic strategy says " + graded.advice + ". ") + graded.reason;
+
+    } else if (msg.hasOwnProperty('CountChecked')) {
+
+      let checked = msg.CountChecked;
+      document.getElementById("count_result").textContent = (checked.correct ? "Right, " : "Not quite, ") + "the running count was " + checked.count;
+
+    } else if (msg.hasOwnProperty('TrainingReport')) {
+
+      let stats = msg.TrainingReport.stats;
+      let report = "Decisions matching basic strategy: " + stats.correct + " / " + stats.decisions +
+        ", running counts right: " + stats.counts_correct + " / " + stats.counts_asked +
+        ", play money " + (stats.net >= 0 ? "won: " : "lost: ") + Math.abs(stats.net);
+      document.getElementById("training_stats").textContent = report;
+      if (msg.TrainingReport.over) {
+        alert("Session over. " + report);
+        location.href = "/";
+      }
+
+    } else if (msg.InviteLink) {
+
+      document.getElementById("invite_link").textContent = msg.InviteLink.link;
+
+    } else if (msg.hasOwnProperty("YourSeats")) {
+
+      my_seats = msg.YourSeats.seats;
+      document.getElementById("my_seats").textContent = my_seats.map(seat => seat + 1).join(", ");
+
+    } else if (msg.hasOwnProperty("NewHost")) {
+
+      is_host = true;
+      playing = msg.NewHost.started;
+      document.getElementById("host_controls").removeAttribute("hidden");
+      let seat = document.getElementById("host_seat");
+      document.getElementById("kick").onclick = function() {
+        ws.send(JSON.stringify({"Kick":{"seat":Number(seat.value)}}));
+      }
+      document.getElementById("make_host").onclick = function() {
+        ws.send(JSON.stringify({"TransferHost":{"seat":Number(seat.value)}}));
+      }
+      document.getElementById("lock").onclick = function() {
+        ws.send(JSON.stringify({"LockTable":{"locked":!locked}}));
+      }
+      document.getElementById("add_bot").onclick = function() {
+        let strategy = document.getElementById("bot_strategy").value;
+        ws.send(JSON.stringify({"AddBot":{"seat":Number(seat.value),"strategy":strategy}}));
+      }
+      show_start_button();
+
+    } else if (msg === "NoLongerHost") {
+
+      is_host = false;
+      document.getElementById("host_controls").hidden = true;
+      show_start_button();
+
+    } else if (msg.hasOwnProperty("HostChanged")) {
+
+      console.log("Seat " + (msg.HostChanged.seat + 1) + " is now the host");
+
+    } else if (msg.hasOwnProperty("TableLocked")) {
+
+      locked = msg.TableLocked.locked;
+      document.getElementById("locked").hidden = !locked;
+      document.getElementById("lock").textContent = locked ? "Unlock table" : "Lock table";
+
+    } else if (msg === "NewRound") {
+
+      clear_table();
+      for (let id in hands) {
+        if (hands[id].second_hand) {
+          delete hands[id];
+        } else {
+          hands[id].bet = 0;
+          show_bet(hands[id].seat);
+        }
       }
 
-    } else if (msg === "RequestBet") {
+    } else if (msg.hasOwnProperty("RequestBet")) {
 
       let bet_button = document.getElementById("bet");
       let bet_value = document.getElementById("bet_label");
@@ -46,6 +314,7 @@ window.onload = function() {
       bet_slider.onchange = function() {
         bet_value.innerHTML = bet_slider.value;
       }
+      document.getElementById("bet_seat").textContent = hands[msg.RequestBet.hand].seat + 1;
       bet_value.removeAttribute("hidden");
       bet_button.removeAttribute("hidden");
       bet_slider.removeAttribute("hidden");
@@ -58,10 +327,20 @@ window.onload = function() {
         bet_slider.max -= bet_slider.value;
       }
 
+    } else if (msg.hasOwnProperty("BetPlaced")) {
+
+      let hand = hands[msg.BetPlaced.hand];
+      hand.bet = msg.BetPlaced.amount;
+      show_bet(hand.seat);
+
     } else if (msg.hasOwnProperty('YourTurn')) {
 
       end_turn_button.disabled = false;
       deal_button.disabled = false;
+      if (hint_button) {
+        hint_button.disabled = false;
+        document.getElementById("hint_text").textContent = "";
+      }
       if (msg.YourTurn.can_split) {
         console.log("You can split");
         split_button.removeAttribute("hidden");
@@ -70,21 +349,23 @@ window.onload = function() {
 
     } else if (msg.hasOwnProperty('PlayerSplit')) {
 
-      let id = "player" + msg.PlayerSplit.player;
-      document.getElementById(id + ".1").removeAttribute("hidden");
-      document.getElementById(id + ".1").appendChild(document.getElementById(id).firstChild);
+      let split = msg.PlayerSplit;
+      hands[split.new_hand] = {"seat": hands[split.hand].seat, "second_hand": true, "bet": 0};
+      hand_div(split.new_hand).removeAttribute("hidden");
+      hand_div(split.new_hand).appendChild(hand_div(split.hand).lastChild);
 
     } else if (msg.hasOwnProperty('EndGame')) {
 
       let res = msg.EndGame.result;
+      let seat = "Seat " + (hands[msg.EndGame.hand].seat + 1);
       if (res === "Lose") {
-        alert("Game has ended. You lost.");
+        alert(seat + ": you lost.");
       } else if (res === "Win") {
-        alert("Game has ended. You won.");
+        alert(seat + ": you won.");
       } else if (res === "Blackjack") {
-        alert("Game has ended. You won (Blackjack).");
+        alert(seat + ": you won (Blackjack).");
       } else if (res === "Push") {
-        alert("Game has ended. You tied.");
+        alert(seat + ": you tied.");
       } else {
         alert("Game has ended.");
       }
@@ -97,87 +378,115 @@ window.onload = function() {
         let card = dealer_hand[idx]
         imgs[idx].src = "/static/cards/" + card.rank + card.suit + ".svg";
       }
-      
-      ws.close();
-      setTimeout(() => location.href = "/", 5000);
-      
-    } else if (msg.hasOwnProperty('PlayerJoin')) {
+      playing = false;
+      show_seat_controls();
+      show_start_button();
 
-      player_count = msg.PlayerJoin.player;
-      for (let i = 0; i < msg.PlayerJoin.player; i++) {
-        let player = document.getElementById("player" + i);
-        player.removeAttribute("hidden");
+    } else if (msg.hasOwnProperty('TableState')) {
+
+      let table = msg.TableState;
+      document.getElementById("spectators").textContent = table.spectators;
+      clear_table();
+      let dealer = document.getElementById("dealer");
+      for (let card of table.dealer) {
+        dealer.appendChild(card_image(card));
+      }
+      for (let seat = 0; seat < 6; seat++) {
+        seat_hand(seat, false).hidden = true;
       }
+      hands = {};
+      for (let seat = 0; seat < 6; seat++) {
+        show_player(seat, null);
+      }
+      for (let hand of table.hands) {
+        hands[hand.id] = {"seat": hand.seat, "second_hand": hand.second_hand, "bet": hand.bet};
+        show_player(hand.seat, hand.player);
+        show_bet(hand.seat);
+        let div = hand_div(hand.id);
+        div.removeAttribute("hidden");
+        for (let card of hand.cards) {
+          div.appendChild(card_image(card));
+        }
+      }
+      playing = table.phase === "Playing";
+      locked = table.locked;
+      document.getElementById("locked").hidden = !locked;
+      show_seat_controls();
+      document.getElementById("chat_log").innerHTML = "";
+      for (let message of table.chat) {
+        show_chat(message);
+      }
+
+    } else if (msg.hasOwnProperty('Chat')) {
+
+      show_chat(msg.Chat);
+
+    } else if (msg.hasOwnProperty('ChatRejected')) {
+
+      let error = document.getElementById("chat_error");
+      error.textContent = msg.ChatRejected.reason;
+      error.removeAttribute("hidden");
+      setTimeout(function() { error.hidden = true; }, 3000);
+
+    } else if (msg.hasOwnProperty('ChatReported')) {
+
+      alert("Thanks, a moderator will look at that message.");
+
+    } else if (msg.hasOwnProperty('Hint')) {
+
+      document.getElementById("hint_text").textContent = msg.Hint.advice + ": " + msg.Hint.reason;
+
+    } else if (msg.hasOwnProperty('Spectators')) {
+
+      document.getElementById("spectators").textContent = msg.Spectators.count;
+
+    } else if (msg.hasOwnProperty('PlayerJoin')) {
+
+      let join = msg.PlayerJoin;
+      hands[join.hand] = {"seat": join.seat, "second_hand": false, "bet": 0};
+      hand_div(join.hand).removeAttribute("hidden");
+      show_player(join.seat, join.player);
+      show_bet(join.seat);
 
     } else if (msg.hasOwnProperty('PlayerLeave')) {
 
-      let player_leaving = document.getElementById("player" + msg.PlayerLeave.player);
-      player_leaving.innerHTML = "";
-      player_leaving.hidden = "true";
-      let player_leaving_split = document.getElementById("player" + msg.PlayerLeave.player + ".1");
-      player_leaving_split.innerHTML = "";
-      player_leaving_split.hidden = "true";
-      for(let i = msg.PlayerLeave.player; i < player_count; i++) {
-        let oldParent = document.getElementById("player" + (i + 1));
-        let newParent = document.getElementById("player" + i);
-        while(oldParent.hasChildNodes()) {
-          console.log("moving card from " + oldParent.id + " to " + newParent.id);
-          newParent.append(oldParent.firstChild);
-        }
-        let oldParentSplit = document.getElementById("player" + (i + 1) + ".1");
-        let newParentSplit = document.getElementById("player" + i + ".1");
-        if (!oldParentSplit.hasAttribute("hidden")) {
-          newParentSplit.removeAttribute("hidden");
-          while(oldParentSplit.hasChildNodes()) {
-            console.log("moving card from " + oldParent.id + ".1 to " + newParent.id);
-            newParentSplit.append(oldParentSplit.firstChild);
-          }
-          oldParentSplit.setAttribute("hidden", "true");
+      let seat = msg.PlayerLeave.seat;
+      for (let id in hands) {
+        if (hands[id].seat === seat) {
+          delete hands[id];
         }
       }
-      console.log("player_count:" + player_count);
-      let player = document.getElementById("player" + (player_count - 1));
-      player.setAttribute("hidden", "true");
-
-      let player_split = document.getElementById("player" + (player_count - 1) + ".1");
-      player_split.setAttribute("hidden", "true");
-      player_count--;
+      for (let second_hand of [false, true]) {
+        seat_hand(seat, second_hand).innerHTML = "";
+        seat_hand(seat, second_hand).hidden = true;
+      }
+      show_player(seat, null);
+      show_bet(seat);
 
     } else if (msg.hasOwnProperty('Dealt')) {
 
       let card = msg.Dealt.card;
-      let img = document.createElement("img");
       if (card !== null) {
-        card = "" + card.rank + " of " + card.suit;
-        console.log("Player " + msg.Dealt.hand + " has recieved the card " + card);
-        img.src = "/static/cards/" + msg.Dealt.card.rank + msg.Dealt.card.suit + ".svg";
-        
+        console.log("Hand " + msg.Dealt.hand + " has recieved the card " + card.rank + " of " + card.suit);
       } else {
-        console.log("Player " + msg.Dealt.hand + " has recieved a card");
-        img.src = "/static/cards/back.svg";
+        console.log("Hand " + msg.Dealt.hand + " has recieved a card");
       }
-      img.style = "width: 20%;";
-      let id = "player" + msg.Dealt.hand;
-      if (msg.Dealt.second_hand) {
-        id += ".1";
+      if (!playing) {
+        playing = true;
+        show_seat_controls();
+        show_start_button();
       }
-      document.getElementById(id).appendChild(img);
+      hand_div(msg.Dealt.hand).appendChild(card_image(card));
 
     } else if (msg.hasOwnProperty("DealDealer")) {
 
       let card = msg.DealDealer.card;
-      let img = document.createElement("img");
       if (card !== null) {
-        card = "" + card.rank + " of " + card.suit;
-        console.log("Dealer has recieved the card " + card);
-        img.src = "/static/cards/" + msg.DealDealer.card.rank + msg.DealDealer.card.suit + ".svg";
-        
+        console.log("Dealer has recieved the card " + card.rank + " of " + card.suit);
       } else {
         console.log("Dealer has recieved a card");
-        img.src = "/static/cards/back.svg";
       }
-      img.style = "width: 20%;";
-      document.getElementById("dealer").appendChild(img);
+      document.getElementById("dealer").appendChild(card_image(card));
 
     }
   }
@@ -0,0 +1,58 @@
+window.onload = function() {
+  let rooms = new Map();
+  let table = document.getElementById("rooms");
+  let empty = document.getElementById("empty");
+
+  function render() {
+    table.innerHTML = "";
+    let ids = Array.from(rooms.keys()).sort();
+    for (let id of ids) {
+      let room = rooms.get(id);
+      let row = document.createElement("tr");
+      let cells = [
+        room.id,
+        room.seats + " / " + room.max_seats + " (" + room.spectators + " watching)",
+        room.rules.min_bet + " - " + room.rules.max_bet,
+        room.summary,
+        room.phase,
+      ];
+      for (let text of cells) {
+        let cell = document.createElement("td");
+        cell.textContent = text;
+        row.appendChild(cell);
+      }
+      let join = document.createElement("td");
+      if (room.joinable) {
+        let link = document.createElement("a");
+        link.href = "/" + room.id;
+        link.textContent = "Join";
+        join.appendChild(link);
+      } else if (room.can_spectate) {
+        let link = document.createElement("a");
+        link.href = "/" + room.id + "?spectate=true";
+        link.textContent = "Watch";
+        join.appendChild(link);
+      }
+      row.appendChild(join);
+      table.appendChild(row);
+    }
+    empty.hidden = rooms.size !== 0;
+  }
+
+  let scheme = window.location.protocol === "https:" ? "wss://" : "ws://";
+  let ws = new WebSocket(scheme + window.location.host + "/lobby/ws");
+  ws.onmessage = function(event) {
+    let msg = JSON.parse(event.data);
+    if (msg.hasOwnProperty("Rooms")) {
+      rooms.clear();
+      for (let room of msg.Rooms) {
+        rooms.set(room.id, room);
+      }
+    } else if (msg.hasOwnProperty("Updated")) {
+      rooms.set(msg.Updated.id, msg.Updated);
+    } else if (msg.hasOwnProperty("Removed")) {
+      rooms.delete(msg.Removed.id);
+    }
+    render();
+  }
+}
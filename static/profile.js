@@ -0,0 +1,24 @@
+fetch("/profile/balance")
+  .then((response) => response.json())
+  .then((points) => {
+    let canvas = document.getElementById("balance");
+    if (points.length < 2) {
+      canvas.replaceWith("Not enough yet to chart");
+      return;
+    }
+    let context = canvas.getContext("2d");
+    let balances = points.map((point) => point.balance);
+    let low = Math.min(...balances);
+    let high = Math.max(...balances);
+    let first = points[0].at;
+    let span = points[points.length - 1].at - first || 1;
+    let x = (point) => ((point.at - first) / span) * (canvas.width - 1);
+    let y = (point) =>
+      canvas.height - 1 - ((point.balance - low) / (high - low || 1)) * (canvas.height - 1);
+    context.beginPath();
+    context.moveTo(x(points[0]), y(points[0]));
+    for (let point of points.slice(1)) {
+      context.lineTo(x(point), y(point));
+    }
+    context.stroke();
+  });